@@ -0,0 +1,52 @@
+/// 编译vendor目录中的libspeex，用于把mdd中的 .spx 发音解码成浏览器可以播放的wav
+fn main() {
+    let sources = [
+        "bits.c",
+        "cb_search.c",
+        "exc_5_64_table.c",
+        "exc_5_256_table.c",
+        "exc_8_128_table.c",
+        "exc_10_16_table.c",
+        "exc_10_32_table.c",
+        "exc_20_32_table.c",
+        "filters.c",
+        "gain_table.c",
+        "gain_table_lbr.c",
+        "hexc_10_32_table.c",
+        "hexc_table.c",
+        "high_lsp_tables.c",
+        "kiss_fft.c",
+        "kiss_fftr.c",
+        "lpc.c",
+        "lsp.c",
+        "lsp_tables_nb.c",
+        "ltp.c",
+        "modes.c",
+        "modes_wb.c",
+        "nb_celp.c",
+        "quant_lsp.c",
+        "sb_celp.c",
+        "smallft.c",
+        "speex.c",
+        "speex_callbacks.c",
+        "speex_header.c",
+        "stereo.c",
+        "vbr.c",
+        "vorbis_psy.c",
+        "vq.c",
+        "window.c",
+    ];
+
+    let mut build = cc::Build::new();
+    build
+        .include("vendor/libspeex/include")
+        .define("FLOATING_POINT", None)
+        .define("EXPORT", "")
+        .warnings(false);
+    for source in sources {
+        build.file(format!("vendor/libspeex/libspeex/{source}"));
+    }
+    build.compile("speex");
+
+    println!("cargo:rerun-if-changed=vendor/libspeex");
+}
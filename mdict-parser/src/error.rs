@@ -0,0 +1,59 @@
+use std::fmt;
+use std::io;
+
+/// 解析和生成mdx/mdd时的错误
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// 数据不完整或结构不符合mdict格式
+    Format(String),
+    /// adler32校验不一致，参数是校验的部分
+    Checksum(&'static str),
+    /// 不支持的引擎版本、加密或压缩方式
+    Unsupported(String),
+    /// 文本无法按词典的编码转换
+    Encoding(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "io error: {e}"),
+            Error::Format(msg) => write!(f, "invalid mdict data: {msg}"),
+            Error::Checksum(part) => write!(f, "{part} adler32 checksum mismatch"),
+            Error::Unsupported(msg) => write!(f, "unsupported: {msg}"),
+            Error::Encoding(msg) => write!(f, "encoding error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+/// nom解析失败说明数据不完整或字段不对
+impl From<nom::Err<nom::error::Error<&[u8]>>> for Error {
+    fn from(e: nom::Err<nom::error::Error<&[u8]>>) -> Error {
+        match e {
+            nom::Err::Incomplete(_) => Error::Format("unexpected end of data".to_string()),
+            nom::Err::Error(e) | nom::Err::Failure(e) => Error::Format(format!(
+                "{:?} failed with {} bytes left",
+                e.code,
+                e.input.len()
+            )),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use adler32::adler32;
+use encoding::{Encoding, all::UTF_16LE};
+use nom::Parser;
+use nom::multi::length_data;
+use nom::number::complete::{be_u32, le_u32};
+use regex::Regex;
+use tracing::info;
+
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    V1,
+    V2,
+}
+
+/// mdx头部信息
+#[derive(Debug, Clone)]
+pub struct Header {
+    // 牛津8/汉语词典3/朗文4都是 V2
+    pub version: Version,
+    /**
+     * encryption flag "0"-no encryption, "1"-encrypt record block, "2"-encrypt key info block
+     * e.g., 牛津8/汉语词典3 "0" 朗文4 "2"
+     */
+    pub encrypted: String,
+    // record bytes encoding, e.g. "UTF-8"
+    pub encoding: String,
+    // header xml中的所有属性，如 Title Description StyleSheet
+    pub attributes: HashMap<String, String>,
+}
+
+impl Header {
+    /// 词头编码单元的字节数，UTF-16为2，其他为1
+    pub fn key_unit(&self) -> usize {
+        if self.encoding.to_uppercase().starts_with("UTF-16") {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// key block info是否加密，Encrypted的第2位，如 "2" "3"
+    pub(crate) fn key_info_encrypted(&self) -> bool {
+        self.encrypted.parse::<u32>().is_ok_and(|flags| flags & 2 != 0)
+    }
+
+    /// 词典标题，没有时为None
+    pub fn title(&self) -> Option<&str> {
+        self.attributes.get("Title").map(|t| t.as_str())
+    }
+
+    /// 词典描述，通常是html
+    pub fn description(&self) -> Option<&str> {
+        self.attributes.get("Description").map(|d| d.as_str())
+    }
+}
+
+/// 解析文件开头的header，返回header之后的数据
+pub fn parse_header(data: &[u8]) -> Result<(&[u8], Header)> {
+    // length_data(be_u32) 先读取一个be_u32 number,然后根据number读取对应长度bytes
+    let (data, (header_buf, checksum)) = (length_data(be_u32), le_u32).parse(data)?;
+    // &[8] 实现Read接口
+    if adler32(header_buf)? != checksum {
+        return Err(Error::Checksum("header"));
+    }
+    let attributes = header_attributes(&header_xml(header_buf)?);
+    info!(">>>the header content: {:?}", &attributes);
+    Ok((data, header_from_attributes(attributes)?))
+}
+
+/// header是UTF-16LE编码的xml
+pub(crate) fn header_xml(header_buf: &[u8]) -> Result<String> {
+    UTF_16LE
+        .decode(header_buf, encoding::DecoderTrap::Strict)
+        .map_err(|e| Error::Encoding(format!("header is not valid UTF-16LE: {e}")))
+}
+
+/// 从header xml的属性得到版本、加密方式和编码
+pub(crate) fn header_from_attributes(attributes: Vec<(String, String)>) -> Result<Header> {
+    let attributes: HashMap<_, _> = attributes.into_iter().collect();
+    let engine = attributes
+        .get("GeneratedByEngineVersion")
+        .ok_or_else(|| Error::Format("header has no GeneratedByEngineVersion".to_string()))?;
+    let version = match engine.trim().chars().next() {
+        Some('1') => Version::V1,
+        Some('2') => Version::V2,
+        _ => {
+            return Err(Error::Unsupported(format!("mdx engine version {engine}")));
+        }
+    };
+
+    // "0" "2" "3"，没有时认为不加密
+    let encrypted = attributes
+        .get("Encrypted")
+        .map_or_else(|| "0".to_string(), |e| e.trim().to_string());
+
+    // "UTF-8", mdd的header中没有或为空，默认UTF-8
+    let encoding = attributes
+        .get("Encoding")
+        .filter(|e| !e.trim().is_empty())
+        .map_or_else(|| "UTF-8".to_string(), |e| e.trim().to_string());
+
+    Ok(Header {
+        version,
+        encrypted,
+        encoding,
+        attributes,
+    })
+}
+
+/// header xml中的属性，按出现顺序，如 [("GeneratedByEngineVersion", "2.0"), ("Encrypted", "0")]
+pub(crate) fn header_attributes(info: &str) -> Vec<(String, String)> {
+    let re = Regex::new(r#"(\w+)="((.|\r\n|[\r\n])*?)""#).unwrap();
+    re.captures_iter(info)
+        .map(|cap| (cap[1].to_string(), cap[2].to_string()))
+        .collect()
+}
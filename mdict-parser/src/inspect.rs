@@ -0,0 +1,441 @@
+use std::collections::BTreeMap;
+
+use adler32::adler32;
+use encoding::DecoderTrap;
+
+use crate::header::{Header, Version, header_attributes, header_from_attributes, header_xml};
+use crate::keyblock::{
+    KeyBlockSize, decode_key_block_info, key_block_items, key_decoder, parse_key_blocks_size,
+};
+use crate::recordblock::decode_block;
+
+/// 检查中发现的一个问题，offset是问题所在位置在文件中的字节偏移
+#[derive(Debug)]
+pub struct Problem {
+    pub offset: usize,
+    pub message: String,
+}
+
+/// key block或record block的统计
+#[derive(Debug, Default)]
+pub struct Blocks {
+    pub count: usize,
+    pub compressed_size: usize,
+    pub decompressed_size: usize,
+    // 压缩方式 -> block数量，如 {"zlib": 3}
+    pub compression: BTreeMap<&'static str, usize>,
+    // 加密方式 -> block数量
+    pub encryption: BTreeMap<&'static str, usize>,
+}
+
+/// mdx/mdd文件的结构信息和检查结果
+#[derive(Debug, Default)]
+pub struct Report {
+    pub file_size: usize,
+    // header中的所有属性，按出现顺序
+    pub attributes: Vec<(String, String)>,
+    pub version: String,
+    pub encoding: String,
+    pub encrypted: String,
+    pub key_block_info_size: usize,
+    pub key_blocks: Blocks,
+    pub record_blocks: Blocks,
+    // key block header中记录的词条数
+    pub entry_num: usize,
+    // verify时从key block中实际解析出的词条数
+    pub decoded_entries: Option<usize>,
+    pub problems: Vec<Problem>,
+}
+
+/// 解析mdx/mdd的结构，遇到错误不panic而是记录到problems中
+/// verify为true时解压每个block并检查adler32校验和、大小和词条
+pub fn inspect(data: &[u8], mdd: bool, verify: bool) -> Report {
+    let mut inspector = Inspector {
+        data,
+        pos: 0,
+        v2: true,
+        verify,
+        report: Report {
+            file_size: data.len(),
+            ..Default::default()
+        },
+    };
+    // 结构错误之后的数据无法定位，停止检查
+    let _ = inspector.run(mdd);
+    inspector.report
+}
+
+struct Inspector<'a> {
+    data: &'a [u8],
+    pos: usize,
+    v2: bool,
+    verify: bool,
+    report: Report,
+}
+
+impl<'a> Inspector<'a> {
+    fn run(&mut self, mdd: bool) -> Option<()> {
+        let header = self.header(mdd)?;
+        let key_blocks_info = self.key_block_section(&header)?;
+        let record_offsets = self.key_blocks(&key_blocks_info, &header)?;
+        self.record_section(record_offsets.as_deref())?;
+        if self.pos < self.data.len() {
+            self.problem(
+                self.pos,
+                format!(
+                    "{} trailing bytes after the last record block",
+                    self.data.len() - self.pos
+                ),
+            );
+        }
+        Some(())
+    }
+
+    /// header的解析和 parse_header 相同，长度和校验和逐项检查以便报告问题
+    fn header(&mut self, mdd: bool) -> Option<Header> {
+        let len = self.u32("header length")?;
+        let start = self.pos;
+        let buf = self.take(len, "header")?;
+        let checksum = u32::from_le_bytes(self.take(4, "header checksum")?.try_into().unwrap());
+        if adler32(buf).unwrap() != checksum {
+            self.problem(start, "header adler32 checksum mismatch".to_string());
+        }
+        let attributes = match header_xml(buf) {
+            Ok(xml) => header_attributes(&xml),
+            Err(e) => {
+                self.problem(start, e.to_string());
+                return None;
+            }
+        };
+        let mut header = match header_from_attributes(attributes.clone()) {
+            Ok(header) => header,
+            Err(e) => {
+                self.problem(start, e.to_string());
+                return None;
+            }
+        };
+        // mdd的词头固定为UTF-16LE，和 Mdd::parse 一致
+        if mdd {
+            header.encoding = "UTF-16LE".to_string();
+        }
+        if let Err(e) = key_decoder(&header) {
+            self.problem(start, e.to_string());
+            return None;
+        }
+        self.v2 = header.version == Version::V2;
+        self.report.version = header.attributes["GeneratedByEngineVersion"].clone();
+        self.report.attributes = attributes;
+        self.report.encrypted = header.encrypted.clone();
+        self.report.encoding = header.encoding.clone();
+        Some(header)
+    }
+
+    /// key block header和key block info，返回每个key block的词条数和大小
+    fn key_block_section(&mut self, header: &Header) -> Option<Vec<KeyBlockSize>> {
+        let start = self.pos;
+        let block_num = self.num("key block count")?;
+        self.report.entry_num = self.num("entry count")?;
+        let info_dsize = if self.v2 {
+            Some(self.num("key block info decompressed size")?)
+        } else {
+            None
+        };
+        let info_len = self.num("key block info size")?;
+        let blocks_len = self.num("key blocks size")?;
+        if self.v2 {
+            let checksum = self.u32("key block header checksum")?;
+            if adler32(&self.data[start..start + 40]).unwrap() != checksum as u32 {
+                self.problem(
+                    start,
+                    "key block header adler32 checksum mismatch".to_string(),
+                );
+            }
+        }
+        self.report.key_block_info_size = info_len;
+
+        let info_start = self.pos;
+        let info = self.take(info_len, "key block info")?;
+        let blocks = match decode_key_block_info(info, info_dsize.unwrap_or(info_len), header)
+            .and_then(|info| parse_key_blocks_size(&info, header))
+        {
+            Ok(blocks) => blocks,
+            Err(e) => {
+                self.problem(info_start, e.to_string());
+                return None;
+            }
+        };
+
+        if blocks.len() != block_num {
+            self.problem(
+                info_start,
+                format!(
+                    "key block info lists {} blocks, header says {block_num}",
+                    blocks.len()
+                ),
+            );
+        }
+        match checked_sum(blocks.iter().map(|b| b.entries)) {
+            Some(entries) if entries == self.report.entry_num => {}
+            Some(entries) => self.problem(
+                info_start,
+                format!(
+                    "key block info lists {entries} entries, header says {}",
+                    self.report.entry_num
+                ),
+            ),
+            None => self.problem(info_start, "key block entry counts overflow".to_string()),
+        }
+        match checked_sum(blocks.iter().map(|b| b.csize)) {
+            Some(csize) if csize == blocks_len => {}
+            Some(csize) => self.problem(
+                info_start,
+                format!("key blocks add up to {csize} bytes, header says {blocks_len}"),
+            ),
+            None => {
+                self.problem(info_start, "key block sizes overflow".to_string());
+                return None;
+            }
+        }
+        Some(blocks)
+    }
+
+    /// 逐个key block统计，verify时解压并返回所有词条的record偏移
+    fn key_blocks(
+        &mut self,
+        infos: &[KeyBlockSize],
+        header: &Header,
+    ) -> Option<Option<Vec<usize>>> {
+        let mut offsets = self.verify.then(Vec::new);
+        for (i, info) in infos.iter().enumerate() {
+            let start = self.pos;
+            let block = self.take(info.csize, "key block")?;
+            self.count_block(false, start, block, info.dsize)?;
+            if !self.verify {
+                continue;
+            }
+            let decoded = match decode_block(block, info.csize, info.dsize) {
+                Ok(decoded) => decoded,
+                Err(e) => {
+                    self.problem(start, format!("key block {i}: {e}"));
+                    offsets = None;
+                    continue;
+                }
+            };
+            match self.key_block_offsets(&decoded, start, header) {
+                Ok(items) => {
+                    if items.len() != info.entries {
+                        self.problem(
+                            start,
+                            format!(
+                                "key block {i} has {} entries, key block info says {}",
+                                items.len(),
+                                info.entries
+                            ),
+                        );
+                    }
+                    if let Some(offsets) = offsets.as_mut() {
+                        offsets.extend(items);
+                    }
+                }
+                Err(e) => {
+                    self.problem(start, format!("key block {i}: {e}"));
+                    offsets = None;
+                }
+            }
+        }
+        if let Some(offsets) = &offsets {
+            self.report.decoded_entries = Some(offsets.len());
+        }
+        Some(offsets)
+    }
+
+    /// key block中所有词条的record偏移，词头不能按词典编码解码时记录问题
+    /// start为block在文件中的位置
+    fn key_block_offsets(
+        &mut self,
+        data: &[u8],
+        start: usize,
+        header: &Header,
+    ) -> crate::error::Result<Vec<usize>> {
+        let decoder = key_decoder(header)?;
+        let mut offsets = vec![];
+        for (offset, key) in key_block_items(data, header)? {
+            if decoder.decode(key, DecoderTrap::Strict).is_err() {
+                self.problem(
+                    start,
+                    format!(
+                        "key at record offset {offset} is not valid {}",
+                        self.report.encoding
+                    ),
+                );
+            }
+            offsets.push(offset);
+        }
+        Ok(offsets)
+    }
+
+    /// record header、record block info和record blocks
+    fn record_section(&mut self, offsets: Option<&[usize]>) -> Option<()> {
+        let start = self.pos;
+        let block_num = self.num("record block count")?;
+        let entry_num = self.num("record entry count")?;
+        let info_len = self.num("record block info size")?;
+        let blocks_len = self.num("record blocks size")?;
+        if entry_num != self.report.entry_num {
+            self.problem(
+                start,
+                format!(
+                    "record header says {entry_num} entries, key block header says {}",
+                    self.report.entry_num
+                ),
+            );
+        }
+        let item_len: usize = if self.v2 { 16 } else { 8 };
+        if block_num.checked_mul(item_len) != Some(info_len) {
+            self.problem(
+                start,
+                format!("record block info is {info_len} bytes for {block_num} blocks"),
+            );
+            return None;
+        }
+        let mut sizes = vec![];
+        for _ in 0..block_num {
+            let csize = self.num("record block compressed size")?;
+            let dsize = self.num("record block decompressed size")?;
+            sizes.push((csize, dsize));
+        }
+        match checked_sum(sizes.iter().map(|&(c, _)| c)) {
+            Some(csize) if csize == blocks_len => {}
+            Some(csize) => self.problem(
+                start,
+                format!("record blocks add up to {csize} bytes, header says {blocks_len}"),
+            ),
+            None => {
+                self.problem(start, "record block sizes overflow".to_string());
+                return None;
+            }
+        }
+
+        for (i, &(csize, dsize)) in sizes.iter().enumerate() {
+            let start = self.pos;
+            let block = self.take(csize, "record block")?;
+            self.count_block(true, start, block, dsize)?;
+            if self.verify
+                && let Err(e) = decode_block(block, csize, dsize)
+            {
+                self.problem(start, format!("record block {i}: {e}"));
+            }
+        }
+
+        // 词条的record偏移应递增，并且在所有record解压后的范围内
+        if let Some(offsets) = offsets {
+            let total = self.report.record_blocks.decompressed_size;
+            if offsets.windows(2).any(|w| w[0] > w[1]) {
+                self.problem(start, "record offsets of keys are not in order".to_string());
+            }
+            if let Some(&last) = offsets.last().filter(|&&o| o >= total) {
+                self.problem(
+                    start,
+                    format!("record offset {last} is beyond the {total} decompressed record bytes"),
+                );
+            }
+        }
+        Some(())
+    }
+
+    fn take(&mut self, len: usize, what: &str) -> Option<&'a [u8]> {
+        let data = self.data;
+        match self.pos.checked_add(len).filter(|&end| end <= data.len()) {
+            Some(end) => {
+                let buf = &data[self.pos..end];
+                self.pos = end;
+                Some(buf)
+            }
+            None => {
+                self.problem(
+                    self.pos,
+                    format!(
+                        "unexpected end of file reading {what}: need {len} bytes, {} left",
+                        data.len() - self.pos
+                    ),
+                );
+                None
+            }
+        }
+    }
+
+    fn u32(&mut self, what: &str) -> Option<usize> {
+        let buf = self.take(4, what)?;
+        Some(u32::from_be_bytes(buf.try_into().unwrap()) as usize)
+    }
+
+    /// v2为be_u64，v1为be_u32
+    fn num(&mut self, what: &str) -> Option<usize> {
+        if self.v2 {
+            let buf = self.take(8, what)?;
+            Some(u64::from_be_bytes(buf.try_into().unwrap()) as usize)
+        } else {
+            self.u32(what)
+        }
+    }
+
+    /// 累计key block或record block的统计，大小之和溢出时记录问题并停止检查
+    fn count_block(
+        &mut self,
+        record: bool,
+        start: usize,
+        block: &[u8],
+        dsize: usize,
+    ) -> Option<()> {
+        let (blocks, what) = if record {
+            (&mut self.report.record_blocks, "record")
+        } else {
+            (&mut self.report.key_blocks, "key")
+        };
+        let sizes = blocks
+            .compressed_size
+            .checked_add(block.len())
+            .zip(blocks.decompressed_size.checked_add(dsize));
+        let Some((compressed_size, decompressed_size)) = sizes else {
+            self.problem(start, format!("{what} block sizes overflow"));
+            return None;
+        };
+        let (compression, encryption) = block_methods(block);
+        blocks.count += 1;
+        blocks.compressed_size = compressed_size;
+        blocks.decompressed_size = decompressed_size;
+        *blocks.compression.entry(compression).or_default() += 1;
+        *blocks.encryption.entry(encryption).or_default() += 1;
+        Some(())
+    }
+
+    fn problem(&mut self, offset: usize, message: String) {
+        self.report.problems.push(Problem { offset, message });
+    }
+}
+
+/// 文件中的大小或数量之和，溢出时为None
+fn checked_sum(mut values: impl Iterator<Item = usize>) -> Option<usize> {
+    values.try_fold(0_usize, usize::checked_add)
+}
+
+/// block开头4个字节的低4位是压缩方式，4-7位是加密方式
+fn block_methods(block: &[u8]) -> (&'static str, &'static str) {
+    let Some(info) = block.get(0..4) else {
+        return ("invalid", "invalid");
+    };
+    let info = u32::from_le_bytes(info.try_into().unwrap());
+    let compression = match info & 0xf {
+        0 => "none",
+        1 => "lzo",
+        2 => "zlib",
+        _ => "unknown",
+    };
+    let encryption = match (info >> 4) & 0xf {
+        0 => "none",
+        1 => "fast",
+        2 => "salsa20",
+        _ => "unknown",
+    };
+    (compression, encryption)
+}
@@ -0,0 +1,304 @@
+use crate::error::{Error, Result};
+use crate::header::{Header, Version};
+use crate::recordblock::decode_block;
+use crate::util::fast_decrypt;
+use crate::util::null_terminated;
+use crate::util::text_len_parser_v1;
+use crate::util::text_len_parser_v2;
+use adler32::adler32;
+use encoding::label::encoding_from_whatwg_label;
+use encoding::{DecoderTrap, EncodingRef};
+use flate2::read::ZlibDecoder;
+use nom::{
+    IResult, Parser,
+    bytes::complete::take,
+    combinator::map,
+    multi::{length_data, many0},
+    number::complete::{be_u32, be_u64},
+};
+use ripemd::{Digest, Ripemd128};
+use std::io::Read;
+
+pub struct KeyBlockHeader {
+    #[allow(unused)]
+    pub block_num: usize,
+    #[allow(unused)]
+    pub entry_num: usize,
+    // only version >= 2
+    #[allow(unused)]
+    pub key_block_info_decompressed_len: usize,
+    pub key_block_info_len: usize,
+    pub key_blocks_len: usize,
+}
+
+/// every key block compressed size and decompressed size
+/// 用于解析出 RecordEntry list
+#[derive(Debug)]
+pub struct KeyBlockSize {
+    // block中的词条数
+    pub entries: usize,
+    pub csize: usize,
+    pub dsize: usize,
+}
+
+/// 词典索引信息, 和实体词典的索引一样，一个text以及一个页码，不过这个页码是整个RecordBlock解压后(叫debuf)的偏移量
+#[derive(Debug)]
+pub struct RecordDeBufOffset {
+    pub text: String,
+    // record在所有RecordBlock解压后的起始位置
+    pub record_offset_in_debuf: usize,
+}
+
+/// key block header的字节数，v2多一个字段和adler32
+pub(crate) fn key_block_header_len(header: &Header) -> usize {
+    match header.version {
+        Version::V1 => 16,
+        Version::V2 => 44,
+    }
+}
+
+pub fn parse_key_block_header<'a>(
+    data: &'a [u8],
+    header: &Header,
+) -> Result<(&'a [u8], KeyBlockHeader)> {
+    return match header.version {
+        Version::V1 => parse_key_block_header_v1(data),
+        Version::V2 => parse_key_block_header_v2(data),
+    };
+
+    fn parse_key_block_header_v1(data: &[u8]) -> Result<(&[u8], KeyBlockHeader)> {
+        let (data, info_buf) = take(16_usize)(data)?;
+        // map 接收一个parser和一个匿名fn, 将parser的结果传递给fn后得到返回值
+        let (_, kbh) = map(
+            (be_u32, be_u32, be_u32, be_u32),
+            |(block_num, entry_num, info_len, blocks_len)| KeyBlockHeader {
+                block_num: block_num as usize,
+                entry_num: entry_num as usize,
+                key_block_info_decompressed_len: info_len as usize, // 没有压缩则相等
+                key_block_info_len: info_len as usize,
+                key_blocks_len: blocks_len as usize,
+            },
+        )
+        .parse(info_buf)?;
+        Ok((data, kbh))
+    }
+
+    fn parse_key_block_header_v2(data: &[u8]) -> Result<(&[u8], KeyBlockHeader)> {
+        // 5个元信息 和 v1相比多了一个key_block_info_decompressed_size 和一个 adler32 checksum
+        let (data, info_buf) = take(40_usize)(data)?;
+        let (data, checksum) = be_u32(data)?;
+
+        // checksum info_buf
+        if adler32(info_buf)? != checksum {
+            return Err(Error::Checksum("key block header"));
+        }
+        let (_, kbh) = map(
+            (be_u64, be_u64, be_u64, be_u64, be_u64),
+            |(
+                block_num,
+                entry_num,
+                key_block_info_decompressed_len,
+                key_block_info_len,
+                key_blocks_len,
+            )| KeyBlockHeader {
+                block_num: block_num as usize,
+                entry_num: entry_num as usize,
+                key_block_info_decompressed_len: key_block_info_decompressed_len as usize,
+                key_block_info_len: key_block_info_len as usize,
+                key_blocks_len: key_blocks_len as usize,
+            },
+        )
+        .parse(info_buf)?;
+        Ok((data, kbh))
+    }
+}
+
+/// Vec<(usize,usize)>: every key block compressed and decompressed size
+/// info_dsize是key block header中记录的解压后大小，v1没有压缩
+pub fn parse_key_block_info<'a>(
+    data: &'a [u8],
+    block_info_len: usize,
+    info_dsize: usize,
+    header: &Header,
+) -> Result<(&'a [u8], Vec<KeyBlockSize>)> {
+    let (data, block_info) = take(block_info_len).parse(data).map_err(Error::from)?;
+    let info = decode_key_block_info(block_info, info_dsize, header)?;
+    Ok((data, parse_key_blocks_size(&info, header)?))
+}
+
+/// v2的key block info: 02000000 + be_u32 adler32 + zlib数据，Encrypted为2或3时zlib数据还经过加密
+/// 解压后检查大小和adler32，v1的key block info原样返回
+pub(crate) fn decode_key_block_info(
+    block_info: &[u8],
+    dsize: usize,
+    header: &Header,
+) -> Result<Vec<u8>> {
+    if header.version == Version::V1 {
+        return Ok(block_info.to_vec());
+    }
+    if block_info.len() < 8 || block_info[0..4] != *b"\x02\x00\x00\x00" {
+        return Err(Error::Format(
+            "key block info is not zlib compressed".to_string(),
+        ));
+    }
+
+    let compressed = if header.key_info_encrypted() {
+        //decrypt
+        let mut md = Ripemd128::new();
+        md.update(&block_info[4..8]);
+        md.update(0x3695_u32.to_le_bytes());
+        fast_decrypt(&block_info[8..], md.finalize().as_slice())
+    } else {
+        Vec::from(&block_info[8..])
+    };
+
+    let mut key_block_info = vec![];
+    ZlibDecoder::new(&compressed[..])
+        .take(dsize as u64 + 1)
+        .read_to_end(&mut key_block_info)
+        .map_err(|e| Error::Format(format!("key block info zlib: {e}")))?;
+    if key_block_info.len() != dsize {
+        return Err(Error::Format(format!(
+            "key block info decompressed to {} bytes, expected {dsize}",
+            key_block_info.len()
+        )));
+    }
+    let checksum = u32::from_be_bytes([block_info[4], block_info[5], block_info[6], block_info[7]]);
+    if adler32(&key_block_info[..])? != checksum {
+        return Err(Error::Checksum("key block info"));
+    }
+    Ok(key_block_info)
+}
+
+/// 解压后的key block info，每项: 词条数, 首词头, 尾词头, 压缩大小, 解压大小
+pub(crate) fn parse_key_blocks_size(info: &[u8], header: &Header) -> Result<Vec<KeyBlockSize>> {
+    let unit = header.key_unit();
+    let (remain, res) = match header.version {
+        Version::V1 => many0(map(
+            (
+                be_u32,
+                length_data(text_len_parser_v1(unit)),
+                length_data(text_len_parser_v1(unit)),
+                be_u32,
+                be_u32,
+            ),
+            |(entries, _, _, csize, dsize)| KeyBlockSize {
+                entries: entries as usize,
+                csize: csize as usize,
+                dsize: dsize as usize,
+            },
+        ))
+        .parse(info)?,
+        Version::V2 => many0(map(
+            (
+                be_u64,
+                length_data(text_len_parser_v2(unit)),
+                length_data(text_len_parser_v2(unit)),
+                be_u64,
+                be_u64,
+            ),
+            |(entries, _, _, csize, dsize)| KeyBlockSize {
+                entries: entries as usize,
+                csize: csize as usize,
+                dsize: dsize as usize,
+            },
+        ))
+        .parse(info)?,
+    };
+    ensure_consumed(remain, "key block info")?;
+    Ok(res)
+}
+
+/// 解析 key blocks
+pub fn parse_key_blocks<'a>(
+    data: &'a [u8],
+    key_blocks_len: usize,
+    header: &Header,
+    key_blocks_size: &[KeyBlockSize],
+) -> Result<(&'a [u8], Vec<RecordDeBufOffset>)> {
+    let (data, mut buf) = take(key_blocks_len).parse(data).map_err(Error::from)?;
+    let decoder = key_decoder(header)?;
+
+    let mut key_entries: Vec<RecordDeBufOffset> = vec![];
+
+    for block_size in key_blocks_size.iter() {
+        key_entries.append(&mut parse_key_block(
+            buf,
+            block_size.csize,
+            block_size.dsize,
+            header,
+            decoder,
+        )?);
+        buf = &buf[block_size.csize..];
+    }
+
+    Ok((data, key_entries))
+}
+
+/// 词头的编码
+pub(crate) fn key_decoder(header: &Header) -> Result<EncodingRef> {
+    encoding_from_whatwg_label(&header.encoding)
+        .ok_or_else(|| Error::Unsupported(format!("encoding {}", header.encoding)))
+}
+
+/// 解析一个 key block，data从block开头开始
+pub(crate) fn parse_key_block(
+    data: &[u8],
+    csize: usize,
+    dsize: usize,
+    header: &Header,
+    decoder: EncodingRef,
+) -> Result<Vec<RecordDeBufOffset>> {
+    let decompressed = decode_block(data, csize, dsize)?;
+    key_block_items(&decompressed, header)?
+        .into_iter()
+        .map(|(offset, text)| {
+            Ok(RecordDeBufOffset {
+                record_offset_in_debuf: offset,
+                text: decoder
+                    .decode(text, DecoderTrap::Ignore)
+                    .map_err(|e| Error::Encoding(e.to_string()))?,
+            })
+        })
+        .collect()
+}
+
+/// 解压后的key block由(record偏移, 以\0结尾的词头)组成，词头不在这里解码
+pub(crate) fn key_block_items<'a>(
+    data: &'a [u8],
+    header: &Header,
+) -> Result<Vec<(usize, &'a [u8])>> {
+    let (remain, items) = match &header.version {
+        Version::V1 => parse_block_items_v1(data, header.key_unit())?,
+        Version::V2 => parse_block_items_v2(data, header.key_unit())?,
+    };
+    ensure_consumed(remain, "key block")?;
+    Ok(items)
+}
+
+/// 返回 (record偏移, 词头bytes)
+fn parse_block_items_v1(data: &[u8], unit: usize) -> IResult<&[u8], Vec<(usize, &[u8])>> {
+    many0(map((be_u32, null_terminated(unit)), |(offset, text)| {
+        (offset as usize, text)
+    }))
+    .parse(data)
+}
+
+fn parse_block_items_v2(data: &[u8], unit: usize) -> IResult<&[u8], Vec<(usize, &[u8])>> {
+    many0(map((be_u64, null_terminated(unit)), |(offset, text)| {
+        (offset as usize, text)
+    }))
+    .parse(data)
+}
+
+/// 解析后不应有剩余的数据
+fn ensure_consumed(remain: &[u8], part: &str) -> Result<()> {
+    if remain.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Format(format!(
+            "{part} has {} bytes left after parsing",
+            remain.len()
+        )))
+    }
+}
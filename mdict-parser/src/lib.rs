@@ -0,0 +1,29 @@
+//! MDict词典文件(mdx/mdd)的解析和生成，不依赖web服务和索引
+//!
+//! ```no_run
+//! use mdict_parser::Mdx;
+//!
+//! let mdx = Mdx::open("oxford.mdx")?;
+//! if let Some(record) = mdx.lookup("apple")? {
+//!     println!("{}", record.definition);
+//! }
+//! # Ok::<(), mdict_parser::Error>(())
+//! ```
+
+pub mod error;
+pub mod header;
+pub mod inspect;
+pub mod mdd;
+pub mod mdx;
+pub mod stream;
+pub mod writer;
+
+mod keyblock;
+mod recordblock;
+mod util;
+
+pub use error::{Error, Result};
+pub use header::{Header, Version};
+pub use mdd::{Mdd, Resource};
+pub use mdx::{Mdx, Record};
+pub use stream::{MdxReader, RecordBuf};
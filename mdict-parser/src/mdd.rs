@@ -0,0 +1,85 @@
+use std::fs;
+use std::path::Path;
+
+use crate::error::Result;
+use crate::header::Header;
+use crate::mdx::Mdx;
+
+/// 一个资源文件，如图片、音频、css
+#[derive(Debug)]
+pub struct Resource<'a> {
+    // mdd中的路径，以\分隔，如 "\uk\apple.spx"
+    pub path: &'a str,
+    pub data: Vec<u8>,
+}
+
+/// MDD 和 MDX 的文件结构相同，区别是词头固定为UTF-16LE编码的资源路径，record是资源文件的bytes
+pub struct Mdd<B = Vec<u8>> {
+    mdx: Mdx<B>,
+}
+
+impl Mdd {
+    /// 读取并解析mdd文件
+    pub fn open(path: impl AsRef<Path>) -> Result<Mdd> {
+        Mdd::from_buffer(fs::read(path)?)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Mdd> {
+        Mdd::from_buffer(data.to_vec())
+    }
+}
+
+impl<B: AsRef<[u8]>> Mdd<B> {
+    /// 从整个文件的数据解析，数据不会被复制
+    pub fn from_buffer(data: B) -> Result<Mdd<B>> {
+        let mdx = Mdx::from_buffer_with(
+            data,
+            |header| {
+                header.encoding = "UTF-16LE".to_string();
+            },
+            normalize,
+        )?;
+        Ok(Mdd { mdx })
+    }
+
+    pub fn header(&self) -> &Header {
+        self.mdx.header()
+    }
+
+    /// 资源文件数
+    pub fn len(&self) -> usize {
+        self.mdx.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mdx.is_empty()
+    }
+
+    /// 按文件中的顺序返回所有资源路径，不解压record
+    pub fn paths(&self) -> impl ExactSizeIterator<Item = &str> {
+        self.mdx.keys()
+    }
+
+    /// 第i个资源文件，i必须小于 len()
+    pub fn resource(&self, i: usize) -> Result<Resource<'_>> {
+        Ok(Resource {
+            path: self.mdx.key(i).unwrap_or_default(),
+            data: self.mdx.raw_record(i)?,
+        })
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = Result<Resource<'_>>> {
+        (0..self.len()).map(|i| self.resource(i))
+    }
+
+    /// 按路径查找资源，忽略大小写，/ 和 \ 都可以作为分隔符，开头的分隔符可以省略
+    pub fn lookup(&self, path: &str) -> Result<Option<Resource<'_>>> {
+        self.mdx.find(path).map(|i| self.resource(i)).transpose()
+    }
+}
+
+fn normalize(path: &str) -> String {
+    path.replace('\\', "/")
+        .trim_start_matches('/')
+        .to_lowercase()
+}
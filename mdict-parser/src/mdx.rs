@@ -0,0 +1,322 @@
+use std::fs;
+use std::path::Path;
+
+use encoding::EncodingRef;
+
+use crate::error::{Error, Result};
+use crate::header::{Header, parse_header};
+use crate::keyblock::{
+    RecordDeBufOffset, key_decoder, parse_key_block_header, parse_key_block_info, parse_key_blocks,
+};
+use crate::recordblock::{RecordBlockSize, decode_block, parse_record_blocks};
+use crate::util::decode_text;
+
+/// 一个record的定位信息：在buf(buf表示所有record_block的bytes)中的offset和在block解压后的offset
+/// draw with: https://asciiflow.com/#/
+//                   ◄──block_csize───►
+//                   ┌────────────────┐
+//            block  │                │
+//                   └────────────────┘
+//                   ▲
+//           block_start_in_buf
+//
+//                   ◄──── block_dsize ───────►
+//                   ┌───┬────────────┬───────┐
+//     block_decomp  │   │   record   │       │
+//                   └───┴────────────┴───────┘
+//                       ▲
+//           record_start_in_de_block
+//
+#[derive(Debug)]
+struct RecordOffsetInfo {
+    text: String,
+    // record所在block在buf的offset 截取block使用
+    block_offset_in_buf: usize,
+    // 解析block使用
+    block_csize: usize,
+    block_dsize: usize,
+    // record在解压后的block的offset 和 end
+    record_start_in_de_block: usize,
+    record_end_in_de_block: usize,
+}
+
+/// 一条释义，text是词头
+#[derive(Debug)]
+pub struct Record<'a> {
+    pub text: &'a str,
+    pub definition: String,
+}
+
+/// MDX 详细结构见 https://bitbucket.org/xwang/mdict-analysis/src/master/MDX.svg
+/// MDX file 结构
+/// header: 得到 version encoding encrypted
+/// key block header: entry number and checksum
+/// key block size info: every key block compressed and decompressed size, for parse key block bytes
+/// key block bytes: 根据上面的key block info得到的（csize,dsize）解析得到 Entry list
+/// record header: record block size, entry number, record block info size, record block size
+/// record block size info: every record block compressed and decompressed size, 用于解析下面的record block
+/// record block bytes: entry and definition bytes, parsed by RecordEntry and RecordBlockSize
+/// record: 是一条释义
+///
+/// B是整个文件的数据，可以是读入内存的 Vec<u8>，也可以是mmap等实现了 AsRef<[u8]> 的类型
+/// 打开时只解析key block得到每个record的位置，读取record时才解压所在的block
+pub struct Mdx<B = Vec<u8>> {
+    header: Header,
+    // header中Encoding对应的解码器，词头和释义都用它解码
+    decoder: EncodingRef,
+    records_offset: Vec<RecordOffsetInfo>,
+    // 词头折叠后的形式，mdx为小写，mdd为统一格式的路径
+    fold: fn(&str) -> String,
+    // 按折叠后的词头排序的序号，lookup时二分查找，相同时保持文件中的顺序
+    sorted: Vec<usize>,
+    data: B,
+    // record block在data中的起始位置
+    records_start: usize,
+}
+
+impl Mdx {
+    /// 读取并解析mdx文件
+    pub fn open(path: impl AsRef<Path>) -> Result<Mdx> {
+        Mdx::from_buffer(fs::read(path)?)
+    }
+
+    /// let data = include_bytes!("/file.mdx");
+    /// let mdx = Mdx::parse(data)?;
+    pub fn parse(data: &[u8]) -> Result<Mdx> {
+        Mdx::from_buffer(data.to_vec())
+    }
+}
+
+impl<B: AsRef<[u8]>> Mdx<B> {
+    /// 从整个文件的数据解析，数据不会被复制
+    pub fn from_buffer(data: B) -> Result<Mdx<B>> {
+        Self::from_buffer_with(data, |_| {}, str::to_lowercase)
+    }
+
+    /// mdd和mdx的结构相同，只是词头编码和record内容不同，adjust在解析索引前修改header
+    /// fold是查询时词头的比较方式
+    pub(crate) fn from_buffer_with(
+        data: B,
+        adjust: impl FnOnce(&mut Header),
+        fold: fn(&str) -> String,
+    ) -> Result<Mdx<B>> {
+        let buf = data.as_ref();
+        let (after_header, mut header) = parse_header(buf)?;
+        adjust(&mut header);
+        let (rest, records_offset) = parse_index(after_header, &header)?;
+        let records_start = buf.len() - rest.len();
+        let mut sorted: Vec<usize> = (0..records_offset.len()).collect();
+        sorted.sort_by_cached_key(|&i| fold(&records_offset[i].text));
+        Ok(Mdx {
+            decoder: key_decoder(&header)?,
+            header,
+            records_offset,
+            fold,
+            sorted,
+            data,
+            records_start,
+        })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// 词条数，包括 @@@LINK= 跳转的词条
+    pub fn len(&self) -> usize {
+        self.records_offset.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records_offset.is_empty()
+    }
+
+    /// 按文件中的顺序返回所有词头，不解压record
+    pub fn keys(&self) -> impl ExactSizeIterator<Item = &str> {
+        self.records_offset.iter().map(|rs| rs.text.as_str())
+    }
+
+    /// 第i个词头，i超出范围时为None
+    pub fn key(&self, i: usize) -> Option<&str> {
+        self.records_offset.get(i).map(|rs| rs.text.as_str())
+    }
+
+    /// 第i条释义，i必须小于 len()
+    pub fn record(&self, i: usize) -> Result<Record<'_>> {
+        let rs = &self.records_offset[i];
+        Ok(Record {
+            text: &rs.text,
+            definition: decode_text(self.decoder, &self.raw_record(i)?),
+        })
+    }
+
+    /// 按文件中的顺序遍历所有释义，每个词条都会重新解压所在的block，只适合小词典或偶尔的遍历
+    /// 导出、索引等完整的遍历使用 MdxReader::records()
+    pub fn items(&self) -> impl Iterator<Item = Result<Record<'_>>> {
+        (0..self.len()).map(|i| self.record(i))
+    }
+
+    /// 不经过索引直接查询词头，先精确匹配，再忽略大小写匹配
+    pub fn lookup(&self, word: &str) -> Result<Option<Record<'_>>> {
+        self.find(word).map(|i| self.record(i)).transpose()
+    }
+
+    /// 二分查找折叠后相同的词头，有完全相同的优先，否则取文件中的第一个
+    pub(crate) fn find(&self, word: &str) -> Option<usize> {
+        let folded = (self.fold)(word);
+        let text = |i: usize| self.records_offset[i].text.as_str();
+        let start = self
+            .sorted
+            .partition_point(|&i| (self.fold)(text(i)) < folded);
+        let mut matches = self.sorted[start..]
+            .iter()
+            .copied()
+            .take_while(|&i| (self.fold)(text(i)) == folded);
+        matches
+            .clone()
+            .find(|&i| text(i) == word)
+            .or_else(|| matches.next())
+    }
+
+    /// 第i个record解压后的原始bytes
+    pub fn raw_record(&self, i: usize) -> Result<Vec<u8>> {
+        read_record(
+            &self.data.as_ref()[self.records_start..],
+            &self.records_offset[i],
+        )
+    }
+}
+
+/// 从header之后的数据解析key block和record block size，返回record block的数据和每个record的位置
+/// 只保存位置信息，record block不复制
+fn parse_index<'a>(data: &'a [u8], header: &Header) -> Result<(&'a [u8], Vec<RecordOffsetInfo>)> {
+    let (data, kbh) = parse_key_block_header(data, header)?;
+    let (data, key_blocks_size) = parse_key_block_info(
+        data,
+        kbh.key_block_info_len,
+        kbh.key_block_info_decompressed_len,
+        header,
+    )?;
+    let (data, entries) = parse_key_blocks(data, kbh.key_blocks_len, header, &key_blocks_size)?;
+    let (data, record_blocks_size) = parse_record_blocks(data, header)?;
+
+    //计算position耗时，一次计算就保存下来
+    Ok((data, records_offset(&entries, &record_blocks_size)?))
+}
+
+/// 在record block数据中解压record所在的block，返回record的原始bytes
+fn read_record(record_block_buf: &[u8], rs: &RecordOffsetInfo) -> Result<Vec<u8>> {
+    // block bytes with tail
+    let block_buf = record_block_buf
+        .get(rs.block_offset_in_buf..)
+        .ok_or_else(|| Error::Format("record block offset out of range".to_string()))?;
+
+    let block_decompressed = decode_block(block_buf, rs.block_csize, rs.block_dsize)?;
+
+    block_decompressed
+        .get(rs.record_start_in_de_block..rs.record_end_in_de_block)
+        .map(|record| record.to_vec())
+        .ok_or_else(|| Error::Format(format!("record {:?} out of block range", rs.text)))
+}
+
+/// bytes structure: buf -> block -> record(entry)
+/// 偏移和大小来自文件，不一致时返回错误而不是溢出
+fn records_offset(
+    records_debuf_index: &[RecordDeBufOffset],
+    record_blocks_size: &[RecordBlockSize],
+) -> Result<Vec<RecordOffsetInfo>> {
+    let overflow = || Error::Format("record offsets do not match record blocks".to_string());
+    let mut positions: Vec<RecordOffsetInfo> = vec![];
+    let mut i: usize = 0;
+    let mut pre_blocks_dsize_sum: usize = 0;
+    let mut pre_blocks_csize_sum: usize = 0;
+    // 同时开始遍历record_blocks_size和entries，每个block包含0或n个entry，
+    // 当entry的buf_decompressed_offset > pre_blocks_dsize_sum时 说明当前block已经遍历结束
+    for block in record_blocks_size {
+        let block_end = pre_blocks_dsize_sum
+            .checked_add(block.dsize)
+            .ok_or_else(overflow)?;
+        while i < records_debuf_index.len() {
+            let record = &records_debuf_index[i];
+
+            // 当前entry已经属于下一个block，注意等于号
+            if record.record_offset_in_debuf >= block_end {
+                break;
+            }
+
+            let record_end_in_de_block = if i < records_debuf_index.len() - 1 {
+                let next_entry = &records_debuf_index[i + 1];
+                next_entry
+                    .record_offset_in_debuf
+                    .checked_sub(pre_blocks_dsize_sum)
+                    .ok_or_else(overflow)?
+            } else {
+                // last entry
+                block.dsize
+            };
+
+            positions.push(RecordOffsetInfo {
+                text: record.text.to_string(),
+                block_offset_in_buf: pre_blocks_csize_sum,
+                block_csize: block.csize,
+                block_dsize: block.dsize,
+                record_start_in_de_block: record
+                    .record_offset_in_debuf
+                    .checked_sub(pre_blocks_dsize_sum)
+                    .ok_or_else(overflow)?,
+                record_end_in_de_block,
+            });
+            i += 1;
+        }
+        pre_blocks_dsize_sum = block_end;
+        pre_blocks_csize_sum = pre_blocks_csize_sum
+            .checked_add(block.csize)
+            .ok_or_else(overflow)?;
+    }
+    Ok(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(text: &str, offset: usize) -> RecordDeBufOffset {
+        RecordDeBufOffset {
+            text: text.to_string(),
+            record_offset_in_debuf: offset,
+        }
+    }
+
+    fn block(csize: usize, dsize: usize) -> RecordBlockSize {
+        RecordBlockSize { csize, dsize }
+    }
+
+    #[test]
+    fn records_in_blocks() {
+        let entries = [entry("a", 0), entry("b", 4), entry("c", 10)];
+        let positions = records_offset(&entries, &[block(8, 10), block(6, 5)]).unwrap();
+        let ranges: Vec<_> = positions
+            .iter()
+            .map(|p| {
+                (
+                    p.block_offset_in_buf,
+                    p.record_start_in_de_block,
+                    p.record_end_in_de_block,
+                )
+            })
+            .collect();
+        assert_eq!(ranges, [(0, 0, 4), (0, 4, 10), (8, 0, 5)]);
+    }
+
+    /// 偏移不递增或大小溢出时返回错误
+    #[test]
+    fn invalid_offsets() {
+        let entries = [entry("a", 12), entry("b", 3)];
+        let result = records_offset(&entries, &[block(8, 10), block(8, 10)]);
+        assert!(matches!(result, Err(Error::Format(_))));
+
+        let entries = [entry("a", 0)];
+        let result = records_offset(&entries, &[block(8, usize::MAX), block(8, 10)]);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+}
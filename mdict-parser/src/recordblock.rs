@@ -0,0 +1,163 @@
+use std::io::Read;
+
+use adler32::adler32;
+use flate2::read::ZlibDecoder;
+use nom::Parser;
+use nom::combinator::map;
+use nom::multi::count;
+use nom::number::complete::{be_u32, be_u64};
+use ripemd::{Digest, Ripemd128};
+
+use crate::error::{Error, Result};
+use crate::header::{Header, Version};
+use crate::util::fast_decrypt;
+
+/// every record block compressed size and decompressed size
+#[derive(Debug)]
+pub struct RecordBlockSize {
+    pub csize: usize,
+    pub dsize: usize,
+}
+
+/// record header的字节数: block数、词条数、record block info长度、record blocks长度
+pub(crate) fn record_header_len(header: &Header) -> usize {
+    match header.version {
+        Version::V1 => 16,
+        Version::V2 => 32,
+    }
+}
+
+/// 从record header中读取record block info的字节数
+pub(crate) fn record_info_len(data: &[u8], header: &Header) -> Result<usize> {
+    let len = match header.version {
+        Version::V1 => (be_u32, be_u32, be_u32).parse(data)?.1.2 as u64,
+        Version::V2 => (be_u64, be_u64, be_u64).parse(data)?.1.2,
+    };
+    usize::try_from(len).map_err(|_| Error::Format(format!("record block info is {len} bytes")))
+}
+
+pub fn parse_record_blocks<'a>(
+    data: &'a [u8],
+    header: &Header,
+) -> Result<(&'a [u8], Vec<RecordBlockSize>)> {
+    match &header.version {
+        Version::V1 => parse_record_blocks_v1(data),
+        Version::V2 => parse_record_blocks_v2(data),
+    }
+}
+
+fn parse_record_blocks_v1(data: &[u8]) -> Result<(&[u8], Vec<RecordBlockSize>)> {
+    let (data, (records_num, _entries_num, record_info_len, _record_buf_len)) =
+        (be_u32, be_u32, be_u32, be_u32).parse(data)?;
+
+    if records_num as u64 * 8 != record_info_len as u64 {
+        return Err(Error::Format(format!(
+            "record block info is {record_info_len} bytes for {records_num} blocks"
+        )));
+    }
+
+    Ok(count(
+        map((be_u32, be_u32), |(csize, dsize)| RecordBlockSize {
+            csize: csize as usize,
+            dsize: dsize as usize,
+        }),
+        records_num as usize,
+    )
+    .parse(data)?)
+}
+
+fn parse_record_blocks_v2(data: &[u8]) -> Result<(&[u8], Vec<RecordBlockSize>)> {
+    let (data, (records_num, _entries_num, record_info_len, _record_buf_len)) =
+        (be_u64, be_u64, be_u64, be_u64).parse(data)?;
+
+    if records_num.checked_mul(16) != Some(record_info_len) {
+        return Err(Error::Format(format!(
+            "record block info is {record_info_len} bytes for {records_num} blocks"
+        )));
+    }
+
+    Ok(count(
+        map((be_u64, be_u64), |(csize, dsize)| RecordBlockSize {
+            csize: csize as usize,
+            dsize: dsize as usize,
+        }),
+        records_num as usize,
+    )
+    .parse(data)?)
+}
+
+/// deflate的最大压缩比约为1032:1，解压后的大小超过 csize 的这个倍数时认为文件已损坏
+const MAX_COMPRESSION_RATIO: usize = 1032;
+
+/// 解压一个key block或record block: 4字节的加密和压缩方式、4字节校验值、数据
+/// data中是block及其之后的数据，只使用前csize个字节，解压后检查大小和adler32
+pub(crate) fn decode_block(data: &[u8], csize: usize, dsize: usize) -> Result<Vec<u8>> {
+    if csize < 8 || data.len() < csize {
+        return Err(Error::Format(format!(
+            "block of {csize} bytes but {} bytes left",
+            data.len()
+        )));
+    }
+    if dsize > csize.saturating_mul(MAX_COMPRESSION_RATIO) {
+        return Err(Error::Format(format!(
+            "block of {csize} bytes cannot decompress to {dsize} bytes"
+        )));
+    }
+    let info = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let checksum = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+    let payload = &data[8..csize];
+
+    let enc_method = (info >> 4) & 0xf;
+    let comp_method = info & 0xf;
+
+    let data: Vec<u8> = match enc_method {
+        0 => Vec::from(payload),
+        1 => {
+            // 8-15位是加密的字节数，为0时整个block都是加密的
+            let size = match ((info >> 8) & 0xff) as usize {
+                0 => payload.len(),
+                size => size.min(payload.len()),
+            };
+            let key = Ripemd128::digest(checksum.to_be_bytes());
+            let mut decrypted = fast_decrypt(&payload[..size], key.as_slice());
+            decrypted.extend_from_slice(&payload[size..]);
+            decrypted
+        }
+        _ => {
+            return Err(Error::Unsupported(format!(
+                "block encryption method {enc_method}"
+            )));
+        }
+    };
+
+    let decompressed = match comp_method {
+        0 => data,
+        1 => minilzo_rs::LZO::init()
+            .and_then(|lzo| lzo.decompress(&data[..], dsize))
+            .map_err(|e| Error::Format(format!("lzo: {e:?}")))?,
+        2 => {
+            // 最多多读一个字节，多出来说明大小不对，不按文件中的大小预先分配
+            let mut v = vec![];
+            ZlibDecoder::new(&data[..])
+                .take(dsize as u64 + 1)
+                .read_to_end(&mut v)
+                .map_err(|e| Error::Format(format!("zlib: {e}")))?;
+            v
+        }
+        _ => {
+            return Err(Error::Unsupported(format!(
+                "block compression method {comp_method}"
+            )));
+        }
+    };
+    if decompressed.len() != dsize {
+        return Err(Error::Format(format!(
+            "block decompressed to {} bytes, expected {dsize}",
+            decompressed.len()
+        )));
+    }
+    if adler32(&decompressed[..])? != checksum {
+        return Err(Error::Checksum("block"));
+    }
+    Ok(decompressed)
+}
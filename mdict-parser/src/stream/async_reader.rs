@@ -0,0 +1,115 @@
+use std::path::Path;
+
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, BufReader, SeekFrom};
+
+use crate::error::{Error, Result};
+use crate::header::Header;
+use crate::stream::machine::{Cursor, Layout, Lookup, Machine, NextRecord, Open, RecordBuf, Step};
+
+/// MdxReader 的异步版本，解析逻辑相同，读取数据时不阻塞运行时
+/// 解压block仍在当前任务中进行，每次只解压一个block
+pub struct AsyncMdxReader<R> {
+    reader: R,
+    layout: Layout,
+}
+
+impl AsyncMdxReader<BufReader<File>> {
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+        AsyncMdxReader::new(BufReader::new(File::open(path).await?)).await
+    }
+
+    pub async fn open_mdd(path: impl AsRef<Path>) -> Result<Self> {
+        AsyncMdxReader::new_mdd(BufReader::new(File::open(path).await?)).await
+    }
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncMdxReader<R> {
+    pub async fn new(reader: R) -> Result<Self> {
+        Self::with_machine(reader, Open::new(false)).await
+    }
+
+    /// mdd的词头是资源路径，record是资源文件的bytes
+    pub async fn new_mdd(reader: R) -> Result<Self> {
+        Self::with_machine(reader, Open::new(true)).await
+    }
+
+    async fn with_machine(mut reader: R, open: Open) -> Result<Self> {
+        let layout = drive(&mut reader, open).await?;
+        Ok(AsyncMdxReader { reader, layout })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.layout.header
+    }
+
+    /// 按文件中的顺序遍历所有record，用 AsyncRecords::next_record 逐个读取
+    pub fn records(&mut self) -> AsyncRecords<'_, R> {
+        AsyncRecords {
+            reader: self,
+            cursor: Cursor::default(),
+            done: false,
+        }
+    }
+
+    /// 逐个读取key block查找词头，先精确匹配，再忽略大小写匹配
+    pub async fn lookup(&mut self, word: &str) -> Result<Option<RecordBuf>> {
+        drive(&mut self.reader, Lookup::new(&self.layout, word)).await
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// AsyncMdxReader::records 返回的record流，出错后结束
+pub struct AsyncRecords<'a, R> {
+    reader: &'a mut AsyncMdxReader<R>,
+    cursor: Cursor,
+    done: bool,
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncRecords<'_, R> {
+    /// 下一个record，遍历结束时为None
+    pub async fn next_record(&mut self) -> Result<Option<RecordBuf>> {
+        if self.done {
+            return Ok(None);
+        }
+        let next = NextRecord {
+            layout: &self.reader.layout,
+            cursor: &mut self.cursor,
+        };
+        let record = drive(&mut self.reader.reader, next).await;
+        self.done = !matches!(record, Ok(Some(_)));
+        record
+    }
+}
+
+/// 按解析过程的要求读取数据，直到得到结果
+async fn drive<R: AsyncRead + AsyncSeek + Unpin, M: Machine>(
+    reader: &mut R,
+    mut machine: M,
+) -> Result<M::Output> {
+    let mut step = machine.resume(None)?;
+    loop {
+        match step {
+            Step::Read { offset, len } => {
+                reader.seek(SeekFrom::Start(offset)).await?;
+                // 长度来自文件，不能直接按长度分配内存
+                let mut buf = vec![];
+                (&mut *reader)
+                    .take(len as u64)
+                    .read_to_end(&mut buf)
+                    .await?;
+                if buf.len() != len {
+                    return Err(Error::Format(format!(
+                        "expected {len} bytes at {offset}, file ends after {}",
+                        buf.len()
+                    )));
+                }
+                step = machine.resume(Some(buf))?;
+            }
+            Step::Done(output) => return Ok(output),
+        }
+    }
+}
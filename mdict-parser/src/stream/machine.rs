@@ -0,0 +1,455 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use encoding::EncodingRef;
+
+use crate::error::{Error, Result};
+use crate::header::{Header, parse_header};
+use crate::keyblock::{
+    RecordDeBufOffset, key_block_header_len, key_decoder, parse_key_block, parse_key_block_header,
+    parse_key_block_info,
+};
+use crate::recordblock::{decode_block, parse_record_blocks, record_header_len, record_info_len};
+use crate::util::decode_text;
+
+/// 一条record，词头和解压后的原始bytes，mdx中是释义，mdd中是资源文件
+#[derive(Clone)]
+pub struct RecordBuf {
+    pub text: String,
+    pub data: Vec<u8>,
+    // header中Encoding对应的解码器
+    decoder: EncodingRef,
+}
+
+impl RecordBuf {
+    /// mdx的释义，按header中的编码解码
+    pub fn definition(&self) -> String {
+        decode_text(self.decoder, &self.data)
+    }
+}
+
+impl fmt::Debug for RecordBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordBuf")
+            .field("text", &self.text)
+            .field("data", &self.data.len())
+            .field("encoding", &self.decoder.name())
+            .finish()
+    }
+}
+
+/// 解析过程中的一步: 读取文件中的一段数据，或者得到结果
+/// 解析逻辑不做io，同步和异步的reader只负责按要求读取数据
+pub(crate) enum Step<T> {
+    Read { offset: u64, len: usize },
+    Done(T),
+}
+
+pub(crate) trait Machine {
+    type Output;
+
+    /// 第一次调用时data为None，之后是上一步要求读取的数据
+    fn resume(&mut self, data: Option<Vec<u8>>) -> Result<Step<Self::Output>>;
+}
+
+/// 一个key block或record block在文件中的位置
+#[derive(Debug)]
+pub(crate) struct Span {
+    offset: u64,
+    csize: usize,
+    dsize: usize,
+    // block在所有block解压后的起始位置，record的偏移相对于它
+    start: usize,
+}
+
+impl Span {
+    fn read<T>(&self) -> Step<T> {
+        Step::Read {
+            offset: self.offset,
+            len: self.csize,
+        }
+    }
+
+    fn contains(&self, offset: usize) -> bool {
+        offset
+            .checked_sub(self.start)
+            .is_some_and(|in_block| in_block < self.dsize)
+    }
+}
+
+/// 文件的header和每个block的位置，打开文件时读取，不包含block的数据
+pub(crate) struct Layout {
+    pub(crate) header: Header,
+    decoder: EncodingRef,
+    key_blocks: Vec<Span>,
+    record_blocks: Vec<Span>,
+}
+
+impl Layout {
+    fn key_block(&self, i: usize, data: &[u8]) -> Result<Vec<RecordDeBufOffset>> {
+        let span = &self.key_blocks[i];
+        parse_key_block(data, span.csize, span.dsize, &self.header, self.decoder)
+    }
+
+    /// 包含offset的record block
+    fn record_block_at(&self, offset: usize) -> Result<usize> {
+        let i = self.record_blocks.partition_point(|span| {
+            offset
+                .checked_sub(span.start)
+                .is_some_and(|in_block| in_block >= span.dsize)
+        });
+        match self.record_blocks.get(i) {
+            Some(span) if span.contains(offset) => Ok(i),
+            _ => Err(Error::Format(format!(
+                "record offset {offset} is beyond the record blocks"
+            ))),
+        }
+    }
+
+    /// 从解压后的record block中截取offset到end之间的record，end为None时到block结尾
+    fn record(&self, i: usize, block: &[u8], offset: usize, end: Option<usize>) -> Result<Vec<u8>> {
+        let span = &self.record_blocks[i];
+        let start = offset - span.start;
+        let end = end.map_or(span.dsize, |end| {
+            end.saturating_sub(span.start).min(span.dsize)
+        });
+        block
+            .get(start..end)
+            .map(|record| record.to_vec())
+            .ok_or_else(|| Error::Format(format!("record at {offset} is out of block range")))
+    }
+}
+
+/// 打开文件: 依次读取header、key block header、key block info、record header、record block info
+pub(crate) enum Open {
+    Start {
+        mdd: bool,
+    },
+    HeaderLen {
+        mdd: bool,
+    },
+    Header {
+        mdd: bool,
+    },
+    KeyBlockHeader {
+        header: Header,
+        pos: u64,
+    },
+    KeyBlockInfo {
+        header: Header,
+        pos: u64,
+        info_dsize: usize,
+        key_blocks_len: usize,
+    },
+    RecordHeader {
+        header: Header,
+        key_blocks: Vec<Span>,
+        pos: u64,
+    },
+    RecordInfo {
+        header: Header,
+        key_blocks: Vec<Span>,
+        pos: u64,
+        record_header: Vec<u8>,
+    },
+    Done,
+}
+
+impl Open {
+    /// mdd的词头固定为UTF-16LE编码的资源路径
+    pub(crate) fn new(mdd: bool) -> Open {
+        Open::Start { mdd }
+    }
+}
+
+impl Machine for Open {
+    type Output = Layout;
+
+    fn resume(&mut self, data: Option<Vec<u8>>) -> Result<Step<Layout>> {
+        let data = data.unwrap_or_default();
+        let (next, step) = match std::mem::replace(self, Open::Done) {
+            Open::Start { mdd } => (Open::HeaderLen { mdd }, Step::Read { offset: 0, len: 4 }),
+            Open::HeaderLen { mdd } => {
+                // be_u32的header长度，之后是header和4字节的adler32，从头读取交给 parse_header
+                let len = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+                (
+                    Open::Header { mdd },
+                    Step::Read {
+                        offset: 0,
+                        len: 4 + len + 4,
+                    },
+                )
+            }
+            Open::Header { mdd } => {
+                let (_, mut header) = parse_header(&data)?;
+                if mdd {
+                    header.encoding = "UTF-16LE".to_string();
+                }
+                let pos = data.len() as u64;
+                let len = key_block_header_len(&header);
+                (
+                    Open::KeyBlockHeader { header, pos },
+                    Step::Read { offset: pos, len },
+                )
+            }
+            Open::KeyBlockHeader { header, pos } => {
+                let (_, kbh) = parse_key_block_header(&data, &header)?;
+                let pos = pos + data.len() as u64;
+                let len = kbh.key_block_info_len;
+                (
+                    Open::KeyBlockInfo {
+                        header,
+                        pos,
+                        info_dsize: kbh.key_block_info_decompressed_len,
+                        key_blocks_len: kbh.key_blocks_len,
+                    },
+                    Step::Read { offset: pos, len },
+                )
+            }
+            Open::KeyBlockInfo {
+                header,
+                pos,
+                info_dsize,
+                key_blocks_len,
+            } => {
+                let (_, sizes) = parse_key_block_info(&data, data.len(), info_dsize, &header)?;
+                let key_blocks_pos = pos + data.len() as u64;
+                let key_blocks = spans(key_blocks_pos, sizes.iter().map(|s| (s.csize, s.dsize)))?;
+                let pos = key_blocks_pos
+                    .checked_add(key_blocks_len as u64)
+                    .ok_or_else(|| {
+                        Error::Format(format!("key blocks are {key_blocks_len} bytes"))
+                    })?;
+                let len = record_header_len(&header);
+                (
+                    Open::RecordHeader {
+                        header,
+                        key_blocks,
+                        pos,
+                    },
+                    Step::Read { offset: pos, len },
+                )
+            }
+            Open::RecordHeader {
+                header,
+                key_blocks,
+                pos,
+            } => {
+                let len = record_info_len(&data, &header)?;
+                let offset = pos + data.len() as u64;
+                (
+                    Open::RecordInfo {
+                        header,
+                        key_blocks,
+                        pos,
+                        record_header: data,
+                    },
+                    Step::Read { offset, len },
+                )
+            }
+            Open::RecordInfo {
+                header,
+                key_blocks,
+                pos,
+                mut record_header,
+            } => {
+                record_header.extend(data);
+                let (_, sizes) = parse_record_blocks(&record_header, &header)?;
+                let record_blocks = spans(
+                    pos + record_header.len() as u64,
+                    sizes.iter().map(|s| (s.csize, s.dsize)),
+                )?;
+                let layout = Layout {
+                    decoder: key_decoder(&header)?,
+                    header,
+                    key_blocks,
+                    record_blocks,
+                };
+                return Ok(Step::Done(layout));
+            }
+            Open::Done => return Err(Error::Format("file is already opened".to_string())),
+        };
+        *self = next;
+        Ok(step)
+    }
+}
+
+/// 连续存放的block，(csize, dsize) 转为在文件中的位置，大小之和溢出时返回错误
+fn spans(mut offset: u64, sizes: impl Iterator<Item = (usize, usize)>) -> Result<Vec<Span>> {
+    let overflow = || Error::Format("block sizes overflow".to_string());
+    let mut start: usize = 0;
+    sizes
+        .map(|(csize, dsize)| {
+            let span = Span {
+                offset,
+                csize,
+                dsize,
+                start,
+            };
+            offset = offset.checked_add(csize as u64).ok_or_else(overflow)?;
+            start = start.checked_add(dsize).ok_or_else(overflow)?;
+            Ok(span)
+        })
+        .collect()
+}
+
+/// 按文件中的顺序遍历record的位置，每次只保留一个key block的词头和一个解压后的record block
+#[derive(Default)]
+pub(crate) struct Cursor {
+    next_key_block: usize,
+    keys: VecDeque<RecordDeBufOffset>,
+    // 当前record block的序号和解压后的数据
+    record_block: usize,
+    block: Option<Vec<u8>>,
+    // 正在读取的block
+    reading: Option<Part>,
+}
+
+enum Part {
+    KeyBlock,
+    RecordBlock,
+}
+
+/// 读取下一个record
+pub(crate) struct NextRecord<'a> {
+    pub(crate) layout: &'a Layout,
+    pub(crate) cursor: &'a mut Cursor,
+}
+
+impl Machine for NextRecord<'_> {
+    type Output = Option<RecordBuf>;
+
+    fn resume(&mut self, data: Option<Vec<u8>>) -> Result<Step<Option<RecordBuf>>> {
+        let (layout, cursor) = (self.layout, &mut *self.cursor);
+        match (cursor.reading.take(), data) {
+            (Some(Part::KeyBlock), Some(data)) => {
+                cursor
+                    .keys
+                    .extend(layout.key_block(cursor.next_key_block, &data)?);
+                cursor.next_key_block += 1;
+            }
+            (Some(Part::RecordBlock), Some(data)) => {
+                let span = &layout.record_blocks[cursor.record_block];
+                cursor.block = Some(decode_block(&data, span.csize, span.dsize)?);
+            }
+            _ => {}
+        }
+
+        // 下一个词头的偏移是当前record的结尾
+        if cursor.keys.len() < 2 && cursor.next_key_block < layout.key_blocks.len() {
+            cursor.reading = Some(Part::KeyBlock);
+            return Ok(layout.key_blocks[cursor.next_key_block].read());
+        }
+        let Some(key) = cursor.keys.front() else {
+            return Ok(Step::Done(None));
+        };
+        let offset = key.record_offset_in_debuf;
+        let i = layout.record_block_at(offset)?;
+        if i != cursor.record_block || cursor.block.is_none() {
+            cursor.record_block = i;
+            cursor.block = None;
+            cursor.reading = Some(Part::RecordBlock);
+            return Ok(layout.record_blocks[i].read());
+        }
+
+        let end = cursor.keys.get(1).map(|next| next.record_offset_in_debuf);
+        let block = cursor.block.as_deref().unwrap_or_default();
+        let data = layout.record(i, block, offset, end)?;
+        let key = cursor.keys.pop_front();
+        Ok(Step::Done(key.map(|key| RecordBuf {
+            text: key.text,
+            data,
+            decoder: layout.decoder,
+        })))
+    }
+}
+
+/// 找到的词头和它的record在所有block解压后的位置
+struct Found {
+    text: String,
+    offset: usize,
+    end: Option<usize>,
+}
+
+/// 逐个读取key block查找词头，先精确匹配，再忽略大小写匹配，找到后只解压一个record block
+pub(crate) struct Lookup<'a> {
+    layout: &'a Layout,
+    word: &'a str,
+    lower: String,
+    next_key_block: usize,
+    exact: Option<Found>,
+    folded: Option<Found>,
+    // 已经确定record的位置，正在读取record block
+    reading: Option<(usize, Found)>,
+}
+
+impl<'a> Lookup<'a> {
+    pub(crate) fn new(layout: &'a Layout, word: &'a str) -> Lookup<'a> {
+        Lookup {
+            layout,
+            word,
+            lower: word.to_lowercase(),
+            next_key_block: 0,
+            exact: None,
+            folded: None,
+            reading: None,
+        }
+    }
+
+    fn scan(&mut self, keys: Vec<RecordDeBufOffset>) {
+        for key in keys {
+            for found in [&mut self.exact, &mut self.folded].into_iter().flatten() {
+                found.end.get_or_insert(key.record_offset_in_debuf);
+            }
+            if self.exact.as_ref().is_some_and(|f| f.end.is_some()) {
+                return;
+            }
+            let candidate = if self.exact.is_none() && key.text == self.word {
+                &mut self.exact
+            } else if self.folded.is_none() && key.text.to_lowercase() == self.lower {
+                &mut self.folded
+            } else {
+                continue;
+            };
+            *candidate = Some(Found {
+                text: key.text,
+                offset: key.record_offset_in_debuf,
+                end: None,
+            });
+        }
+    }
+}
+
+impl Machine for Lookup<'_> {
+    type Output = Option<RecordBuf>;
+
+    fn resume(&mut self, data: Option<Vec<u8>>) -> Result<Step<Option<RecordBuf>>> {
+        if let Some((i, found)) = self.reading.take() {
+            let span = &self.layout.record_blocks[i];
+            let block = decode_block(&data.unwrap_or_default(), span.csize, span.dsize)?;
+            let data = self.layout.record(i, &block, found.offset, found.end)?;
+            return Ok(Step::Done(Some(RecordBuf {
+                text: found.text,
+                data,
+                decoder: self.layout.decoder,
+            })));
+        }
+
+        if let Some(data) = data {
+            let keys = self.layout.key_block(self.next_key_block, &data)?;
+            self.next_key_block += 1;
+            self.scan(keys);
+        }
+        let exact_done = self.exact.as_ref().is_some_and(|f| f.end.is_some());
+        if !exact_done && self.next_key_block < self.layout.key_blocks.len() {
+            return Ok(self.layout.key_blocks[self.next_key_block].read());
+        }
+
+        let Some(found) = self.exact.take().or_else(|| self.folded.take()) else {
+            return Ok(Step::Done(None));
+        };
+        let i = self.layout.record_block_at(found.offset)?;
+        self.reading = Some((i, found));
+        Ok(self.layout.record_blocks[i].read())
+    }
+}
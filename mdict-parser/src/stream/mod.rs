@@ -0,0 +1,11 @@
+//! 从 Read + Seek 或异步reader流式解析mdx/mdd，不需要把整个文件读入内存
+
+#[cfg(feature = "tokio")]
+mod async_reader;
+mod machine;
+mod reader;
+
+#[cfg(feature = "tokio")]
+pub use async_reader::{AsyncMdxReader, AsyncRecords};
+pub use machine::RecordBuf;
+pub use reader::{MdxReader, Records};
@@ -0,0 +1,109 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::header::Header;
+use crate::stream::machine::{Cursor, Layout, Lookup, Machine, NextRecord, Open, RecordBuf, Step};
+
+/// 从 Read + Seek 流式读取mdx或mdd，打开时只读取header和每个block的大小
+/// 遍历时每次只读取并解压一个key block和一个record block，内存占用和词典大小无关
+pub struct MdxReader<R> {
+    reader: R,
+    layout: Layout,
+}
+
+impl MdxReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        MdxReader::new(BufReader::new(File::open(path)?))
+    }
+
+    pub fn open_mdd(path: impl AsRef<Path>) -> Result<Self> {
+        MdxReader::new_mdd(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> MdxReader<R> {
+    pub fn new(reader: R) -> Result<Self> {
+        Self::with_machine(reader, Open::new(false))
+    }
+
+    /// mdd的词头是资源路径，record是资源文件的bytes
+    pub fn new_mdd(reader: R) -> Result<Self> {
+        Self::with_machine(reader, Open::new(true))
+    }
+
+    fn with_machine(mut reader: R, open: Open) -> Result<Self> {
+        let layout = drive(&mut reader, open)?;
+        Ok(MdxReader { reader, layout })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.layout.header
+    }
+
+    /// 按文件中的顺序遍历所有record，每个block只解压一次
+    pub fn records(&mut self) -> Records<'_, R> {
+        Records {
+            reader: self,
+            cursor: Cursor::default(),
+            done: false,
+        }
+    }
+
+    /// 逐个读取key block查找词头，先精确匹配，再忽略大小写匹配
+    pub fn lookup(&mut self, word: &str) -> Result<Option<RecordBuf>> {
+        drive(&mut self.reader, Lookup::new(&self.layout, word))
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// MdxReader::records 返回的迭代器，出错后结束
+pub struct Records<'a, R> {
+    reader: &'a mut MdxReader<R>,
+    cursor: Cursor,
+    done: bool,
+}
+
+impl<R: Read + Seek> Iterator for Records<'_, R> {
+    type Item = Result<RecordBuf>;
+
+    fn next(&mut self) -> Option<Result<RecordBuf>> {
+        if self.done {
+            return None;
+        }
+        let next = NextRecord {
+            layout: &self.reader.layout,
+            cursor: &mut self.cursor,
+        };
+        let record = drive(&mut self.reader.reader, next).transpose();
+        self.done = !matches!(record, Some(Ok(_)));
+        record
+    }
+}
+
+/// 按解析过程的要求读取数据，直到得到结果
+fn drive<R: Read + Seek, M: Machine>(reader: &mut R, mut machine: M) -> Result<M::Output> {
+    let mut step = machine.resume(None)?;
+    loop {
+        match step {
+            Step::Read { offset, len } => {
+                reader.seek(SeekFrom::Start(offset))?;
+                // 长度来自文件，不能直接按长度分配内存
+                let mut buf = vec![];
+                reader.by_ref().take(len as u64).read_to_end(&mut buf)?;
+                if buf.len() != len {
+                    return Err(Error::Format(format!(
+                        "expected {len} bytes at {offset}, file ends after {}",
+                        buf.len()
+                    )));
+                }
+                step = machine.resume(Some(buf))?;
+            }
+            Step::Done(output) => return Ok(output),
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use encoding::{DecoderTrap, EncodingRef};
+use nom::combinator::map;
+use nom::number::complete::{be_u8, be_u16};
+use nom::{IResult, Parser};
+
+// 解压缩这个地方优化一下
+pub(crate) fn fast_decrypt(encrypted: &[u8], key: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::from(encrypted);
+    let mut prev = 0x36;
+    for i in 0..buf.len() {
+        let mut t = buf[i].rotate_left(4);
+        t = t ^ prev ^ (i as u8) ^ key[i % key.len()];
+        prev = buf[i];
+        buf[i] = t;
+    }
+    buf
+}
+
+/// fast_decrypt 的逆运算，写入加密的key block info时使用
+pub(crate) fn fast_encrypt(plain: &[u8], key: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::from(plain);
+    let mut prev = 0x36;
+    for i in 0..buf.len() {
+        let t = buf[i] ^ prev ^ (i as u8) ^ key[i % key.len()];
+        buf[i] = t.rotate_right(4);
+        prev = buf[i];
+    }
+    buf
+}
+
+/// nom parser, key block info中首尾词头的长度，v2的长度不含结尾的\0，unit是编码单元的字节数(UTF-16为2)
+pub(crate) fn text_len_parser_v2<'a>(
+    unit: usize,
+) -> impl Parser<&'a [u8], Output = usize, Error = nom::error::Error<&'a [u8]>> {
+    map(be_u16, move |len| (len as usize + 1) * unit)
+}
+
+pub(crate) fn text_len_parser_v1<'a>(
+    unit: usize,
+) -> impl Parser<&'a [u8], Output = usize, Error = nom::error::Error<&'a [u8]>> {
+    map(be_u8, move |len| len as usize * unit)
+}
+
+/// 以\0结尾的词头，UTF-16编码时结尾是对齐的两个\0，返回不含结尾的bytes
+pub(crate) fn null_terminated(unit: usize) -> impl Fn(&[u8]) -> IResult<&[u8], &[u8]> {
+    move |input: &[u8]| {
+        let end = input
+            .chunks(unit)
+            .position(|c| c.iter().all(|&b| b == 0))
+            .map(|i| i * unit)
+            .ok_or_else(|| {
+                nom::Err::Error(nom::error::Error::new(
+                    input,
+                    nom::error::ErrorKind::TakeUntil,
+                ))
+            })?;
+        Ok((&input[end + unit..], &input[..end]))
+    }
+}
+
+/// 转义xml特殊字符，用于header中的属性值
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// 按词典的编码解码释义，无法解码的字节替换为U+FFFD
+pub(crate) fn decode_text(decoder: EncodingRef, bytes: &[u8]) -> String {
+    if decoder.name() == "utf-8" {
+        return String::from_utf8_lossy(bytes).into_owned();
+    }
+    decoder
+        .decode(bytes, DecoderTrap::Replace)
+        .unwrap_or_else(|e| e.into_owned())
+}
@@ -0,0 +1,542 @@
+use std::io::Write;
+
+use adler32::adler32;
+use encoding::label::encoding_from_whatwg_label;
+use encoding::{EncoderTrap, EncodingRef};
+use flate2::Compression as ZlibLevel;
+use flate2::write::ZlibEncoder;
+use ripemd::{Digest, Ripemd128};
+
+use crate::error::{Error, Result};
+use crate::util::{escape_html, fast_encrypt};
+
+/// 写入block时的压缩方式，值与block开头4个字节中的压缩方式相同
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None = 0,
+    Lzo = 1,
+    Zlib = 2,
+}
+
+impl Compression {
+    /// "none" "lzo" "zlib"
+    pub fn parse(name: &str) -> Option<Compression> {
+        match name.trim().to_lowercase().as_str() {
+            "none" => Some(Compression::None),
+            "lzo" => Some(Compression::Lzo),
+            "zlib" => Some(Compression::Zlib),
+            _ => None,
+        }
+    }
+}
+
+/// 生成2.0版本的mdx文件，结构和 Mdx::parse 解析的相同
+/// header、key block header、key block info、key blocks、record header、record block info、record blocks
+pub struct MdxWriter {
+    pub title: String,
+    pub description: String,
+    // 词头和释义的编码
+    pub encoding: String,
+    pub compression: Compression,
+    // Encrypted="2"，加密key block info
+    pub encrypt_key_info: bool,
+    // key block和record block解压后的目标大小
+    pub block_size: usize,
+}
+
+impl Default for MdxWriter {
+    fn default() -> Self {
+        MdxWriter {
+            title: String::new(),
+            description: String::new(),
+            encoding: "UTF-8".to_string(),
+            compression: Compression::Zlib,
+            encrypt_key_info: false,
+            block_size: 64 * 1024,
+        }
+    }
+}
+
+impl MdxWriter {
+    /// 写入(词头,释义)列表，词条按mdict的词头顺序排序后写入
+    pub fn write(&self, entries: &[(String, String)], out: &mut impl Write) -> Result<()> {
+        let encoder = encoding_from_whatwg_label(&self.encoding)
+            .ok_or_else(|| Error::Unsupported(format!("encoding {}", self.encoding)))?;
+        let mut sorted: Vec<_> = entries.iter().collect();
+        sorted.sort_by_cached_key(|(text, _)| (strip_key(text), text.clone()));
+
+        let mut items = Vec::with_capacity(sorted.len());
+        for (text, definition) in sorted {
+            // 释义以\0结尾，读取时去掉
+            let mut record = encode(encoder, definition)?;
+            record.extend(terminator(encoder));
+            items.push((encode(encoder, text)?, record));
+        }
+
+        let attrs = [
+            ("GeneratedByEngineVersion", "2.0".to_string()),
+            ("RequiredEngineVersion", "2.0".to_string()),
+            ("Encrypted", self.encrypted().to_string()),
+            ("Encoding", self.encoding.clone()),
+            ("Format", "Html".to_string()),
+            ("Stripkey", "Yes".to_string()),
+            ("Compact", "Yes".to_string()),
+            ("Compat", "Yes".to_string()),
+            ("KeyCaseSensitive", "No".to_string()),
+            ("Description", self.description.clone()),
+            ("Title", self.title.clone()),
+            ("DataSourceFormat", "106".to_string()),
+            ("StyleSheet", String::new()),
+            ("Left2Right", "Yes".to_string()),
+            ("RegisterBy", String::new()),
+        ];
+        self.write_dictionary("Dictionary", &attrs, &items, terminator(encoder).len(), out)
+    }
+
+    /// 写入mdd，resources是(资源路径,文件内容)，路径如 "\img\a.png"
+    /// 词头固定为UTF-16LE，record是文件内容，压缩和加密使用同样的选项
+    pub fn write_mdd(&self, resources: &[(String, Vec<u8>)], out: &mut impl Write) -> Result<()> {
+        let mut sorted: Vec<_> = resources.iter().collect();
+        sorted.sort_by_cached_key(|(path, _)| (path.to_lowercase(), path.clone()));
+        let items: Vec<_> = sorted
+            .into_iter()
+            .map(|(path, data)| {
+                let key = path.encode_utf16().flat_map(u16::to_le_bytes).collect();
+                (key, data.clone())
+            })
+            .collect();
+
+        let attrs = [
+            ("GeneratedByEngineVersion", "2.0".to_string()),
+            ("RequiredEngineVersion", "2.0".to_string()),
+            ("Encrypted", self.encrypted().to_string()),
+            ("Format", String::new()),
+            ("Compact", "No".to_string()),
+            ("Compat", "No".to_string()),
+            ("KeyCaseSensitive", "No".to_string()),
+            ("Stripkey", "No".to_string()),
+            ("Description", self.description.clone()),
+            ("Title", self.title.clone()),
+            ("DataSourceFormat", "106".to_string()),
+            ("StyleSheet", String::new()),
+            ("RegisterBy", String::new()),
+            ("RegCode", String::new()),
+        ];
+        self.write_dictionary("Library_Data", &attrs, &items, 2, out)
+    }
+
+    fn encrypted(&self) -> &'static str {
+        if self.encrypt_key_info { "2" } else { "0" }
+    }
+
+    /// mdx和mdd共用的写入过程，items是已排好序的(编码后的词头, record)
+    /// unit是词头编码单元的字节数
+    pub(crate) fn write_dictionary(
+        &self,
+        root: &str,
+        attrs: &[(&str, String)],
+        items: &[(Vec<u8>, Vec<u8>)],
+        unit: usize,
+        out: &mut impl Write,
+    ) -> Result<()> {
+        // header: be_u32长度 + UTF-16LE的xml + le_u32 adler32
+        let mut xml = format!("<{root}");
+        for (name, value) in attrs {
+            xml.push_str(&format!(r#" {name}="{}""#, escape_html(value)));
+        }
+        xml.push_str("/>\r\n\0");
+        let header: Vec<u8> = xml.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        out.write_all(&(header.len() as u32).to_be_bytes())?;
+        out.write_all(&header)?;
+        out.write_all(&adler32(&header[..])?.to_le_bytes())?;
+
+        // record在所有record解压后的偏移
+        let mut offsets = Vec::with_capacity(items.len());
+        let mut offset = 0_u64;
+        for (_, record) in items {
+            offsets.push(offset);
+            offset += record.len() as u64;
+        }
+
+        // key blocks，每项: be_u64 record偏移 + 以\0结尾的词头
+        let mut key_blocks = vec![];
+        let mut key_block_info = vec![];
+        for chunk in self.split(items, |(key, _)| 8 + key.len() + unit) {
+            let mut data = vec![];
+            for i in chunk.clone() {
+                data.extend(offsets[i].to_be_bytes());
+                data.extend(&items[i].0);
+                data.extend(std::iter::repeat_n(0, unit));
+            }
+            let block = self.block(&data)?;
+            // 词条数, 首词头, 尾词头, 压缩大小, 解压大小，词头长度不含\0，单位是编码单元
+            key_block_info.extend((chunk.len() as u64).to_be_bytes());
+            for key in [&items[chunk.start].0, &items[chunk.end - 1].0] {
+                key_block_info.extend(((key.len() / unit) as u16).to_be_bytes());
+                key_block_info.extend(key);
+                key_block_info.extend(std::iter::repeat_n(0, unit));
+            }
+            key_block_info.extend((block.len() as u64).to_be_bytes());
+            key_block_info.extend((data.len() as u64).to_be_bytes());
+            key_blocks.push(block);
+        }
+
+        // key block info: 02000000 + be_u32 adler32 + zlib数据，Encrypted="2"时zlib数据是加密的
+        let checksum = adler32(&key_block_info[..])?.to_be_bytes();
+        let mut compressed = zlib(&key_block_info)?;
+        if self.encrypt_key_info {
+            let mut md = Ripemd128::new();
+            md.update(checksum);
+            md.update(0x3695_u32.to_le_bytes());
+            compressed = fast_encrypt(&compressed, md.finalize().as_slice());
+        }
+        let mut info = b"\x02\x00\x00\x00".to_vec();
+        info.extend(checksum);
+        info.extend(compressed);
+
+        let key_blocks_len: usize = key_blocks.iter().map(Vec::len).sum();
+        let mut kbh = vec![];
+        for n in [
+            key_blocks.len(),
+            items.len(),
+            key_block_info.len(),
+            info.len(),
+            key_blocks_len,
+        ] {
+            kbh.extend((n as u64).to_be_bytes());
+        }
+        out.write_all(&kbh)?;
+        out.write_all(&adler32(&kbh[..])?.to_be_bytes())?;
+        out.write_all(&info)?;
+        for block in &key_blocks {
+            out.write_all(block)?;
+        }
+
+        // record blocks，一个record不会跨block
+        let mut record_blocks = vec![];
+        for chunk in self.split(items, |(_, record)| record.len()) {
+            let data: Vec<u8> = items[chunk]
+                .iter()
+                .flat_map(|(_, r)| r.iter().copied())
+                .collect();
+            record_blocks.push((self.block(&data)?, data.len()));
+        }
+        let record_blocks_len: usize = record_blocks.iter().map(|(b, _)| b.len()).sum();
+        for n in [
+            record_blocks.len(),
+            items.len(),
+            record_blocks.len() * 16,
+            record_blocks_len,
+        ] {
+            out.write_all(&(n as u64).to_be_bytes())?;
+        }
+        for (block, dsize) in &record_blocks {
+            out.write_all(&(block.len() as u64).to_be_bytes())?;
+            out.write_all(&(*dsize as u64).to_be_bytes())?;
+        }
+        for (block, _) in &record_blocks {
+            out.write_all(block)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// 按block_size把items分成连续的几段，每段至少一项
+    fn split<T>(&self, items: &[T], size: impl Fn(&T) -> usize) -> Vec<std::ops::Range<usize>> {
+        let mut ranges = vec![];
+        let (mut start, mut len) = (0, 0);
+        for (i, item) in items.iter().enumerate() {
+            let n = size(item);
+            if i > start && len + n > self.block_size {
+                ranges.push(start..i);
+                start = i;
+                len = 0;
+            }
+            len += n;
+        }
+        if start < items.len() {
+            ranges.push(start..items.len());
+        }
+        ranges
+    }
+
+    /// 压缩一个block: le_u32 压缩方式 + be_u32 解压后数据的adler32 + 压缩数据
+    fn block(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut block = (self.compression as u32).to_le_bytes().to_vec();
+        block.extend(adler32(data)?.to_be_bytes());
+        match self.compression {
+            Compression::None => block.extend(data),
+            Compression::Lzo => {
+                let lzo_error = |e| Error::Format(format!("lzo: {e:?}"));
+                let mut lzo = minilzo_rs::LZO::init().map_err(lzo_error)?;
+                block.extend(lzo.compress(data).map_err(lzo_error)?);
+            }
+            Compression::Zlib => block.extend(zlib(data)?),
+        }
+        Ok(block)
+    }
+}
+
+fn zlib(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(vec![], ZlibLevel::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+pub(crate) fn encode(encoder: EncodingRef, text: &str) -> Result<Vec<u8>> {
+    encoder.encode(text, EncoderTrap::Strict).map_err(|e| {
+        Error::Encoding(format!(
+            "{text:?} can not be encoded as {}: {e}",
+            encoder.name()
+        ))
+    })
+}
+
+/// 编码后的\0，UTF-16为2个字节
+fn terminator(encoder: EncodingRef) -> Vec<u8> {
+    encoder.encode("\0", EncoderTrap::Strict).unwrap_or(vec![0])
+}
+
+/// mdict排序用的词头: 小写并去掉标点和空白(Stripkey="Yes")
+pub(crate) fn strip_key(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_ascii_punctuation())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{Mdd, Mdx, MdxReader};
+
+    fn entries() -> Vec<(String, String)> {
+        let mut entries: Vec<(String, String)> = (0..300)
+            .map(|i| (format!("word{i:03}"), format!("<b>释义 {i}</b> definition")))
+            .collect();
+        entries.push(("Apple".to_string(), "苹果".to_string()));
+        entries.push(("中文".to_string(), "<i>汉语</i>".to_string()));
+        entries
+    }
+
+    /// 写入后用 Mdx 和 MdxReader 读回，词头和释义都和写入的一致
+    fn round_trip(writer: &MdxWriter) {
+        let entries = entries();
+        let mut buf = vec![];
+        writer.write(&entries, &mut buf).unwrap();
+
+        let mdx = Mdx::parse(&buf).unwrap();
+        assert_eq!(mdx.len(), entries.len());
+        assert_eq!(mdx.header().title(), Some(writer.title.as_str()));
+        for (text, definition) in &entries {
+            let record = mdx.lookup(text).unwrap().unwrap();
+            assert_eq!(record.text, text);
+            assert_eq!(record.definition.trim_end_matches('\0'), definition);
+        }
+        let record = mdx.lookup("apple").unwrap().unwrap();
+        assert_eq!(record.text, "Apple");
+
+        let mut reader = MdxReader::new(Cursor::new(&buf)).unwrap();
+        let streamed: Vec<_> = reader.records().collect::<Result<_>>().unwrap();
+        assert_eq!(streamed.len(), entries.len());
+        for (i, record) in streamed.iter().enumerate() {
+            let expected = mdx.record(i).unwrap();
+            assert_eq!(record.text, expected.text);
+            assert_eq!(record.definition(), expected.definition);
+        }
+        let found = reader.lookup("中文").unwrap().unwrap();
+        assert_eq!(found.definition().trim_end_matches('\0'), "<i>汉语</i>");
+    }
+
+    #[test]
+    fn round_trip_compressions_and_encryption() {
+        for compression in [Compression::None, Compression::Lzo, Compression::Zlib] {
+            for encrypt_key_info in [false, true] {
+                round_trip(&MdxWriter {
+                    title: "test".to_string(),
+                    compression,
+                    encrypt_key_info,
+                    block_size: 256,
+                    ..MdxWriter::default()
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_encodings() {
+        for encoding in ["UTF-8", "UTF-16LE", "GBK"] {
+            round_trip(&MdxWriter {
+                title: encoding.to_string(),
+                encoding: encoding.to_string(),
+                block_size: 256,
+                ..MdxWriter::default()
+            });
+        }
+    }
+
+    #[test]
+    fn round_trip_mdd() {
+        let resources = vec![
+            (
+                "\\img\\a.png".to_string(),
+                vec![0x89, b'P', b'N', b'G', 0, 1],
+            ),
+            ("\\uk\\apple.spx".to_string(), vec![7; 1000]),
+        ];
+        let writer = MdxWriter {
+            compression: Compression::Lzo,
+            encrypt_key_info: true,
+            block_size: 128,
+            ..MdxWriter::default()
+        };
+        let mut buf = vec![];
+        writer.write_mdd(&resources, &mut buf).unwrap();
+        let mdd = Mdd::parse(&buf).unwrap();
+        assert_eq!(mdd.len(), 2);
+        let resource = mdd.lookup("UK/Apple.spx").unwrap().unwrap();
+        assert_eq!(resource.data, resources[1].1);
+    }
+
+    #[test]
+    fn unknown_encoding() {
+        let writer = MdxWriter {
+            encoding: "no-such-encoding".to_string(),
+            ..MdxWriter::default()
+        };
+        let result = writer.write(&entries(), &mut vec![]);
+        assert!(matches!(result, Err(Error::Unsupported(_))));
+    }
+
+    /// 未压缩的record被改动后，解析和inspect都能发现adler32不一致
+    #[test]
+    fn corrupted_record_block() {
+        let writer = MdxWriter {
+            compression: Compression::None,
+            ..MdxWriter::default()
+        };
+        let entries = entries();
+        let mut buf = vec![];
+        writer.write(&entries, &mut buf).unwrap();
+        let last = buf.len() - 2;
+        buf[last] ^= 0xff;
+
+        let mdx = Mdx::parse(&buf).unwrap();
+        let result = mdx.record(mdx.len() - 1);
+        assert!(matches!(result, Err(Error::Checksum(_))));
+
+        let report = crate::inspect::inspect(&buf, false, true);
+        assert!(
+            report
+                .problems
+                .iter()
+                .any(|p| p.message.contains("adler32 checksum mismatch"))
+        );
+    }
+
+    fn be_u64(buf: &[u8], at: usize) -> u64 {
+        u64::from_be_bytes(buf[at..at + 8].try_into().unwrap())
+    }
+
+    /// record header的位置: block数、词条数、record block info长度、record blocks长度
+    /// 之后是每个block的(csize, dsize)，最后是所有record block
+    fn record_header_at(buf: &[u8]) -> usize {
+        (0..buf.len() - 32)
+            .find(|&p| {
+                let (num, info_len, blocks_len) =
+                    (be_u64(buf, p), be_u64(buf, p + 16), be_u64(buf, p + 24));
+                num > 0
+                    && num.checked_mul(16) == Some(info_len)
+                    && (p as u64 + 32).checked_add(info_len + blocks_len) == Some(buf.len() as u64)
+            })
+            .unwrap()
+    }
+
+    /// 多个record block的mdx，返回文件和record header的位置
+    fn mdx_with_blocks() -> (Vec<u8>, usize) {
+        let writer = MdxWriter {
+            block_size: 256,
+            ..MdxWriter::default()
+        };
+        let mut buf = vec![];
+        writer.write(&entries(), &mut buf).unwrap();
+        let at = record_header_at(&buf);
+        assert!(be_u64(&buf, at) > 1);
+        (buf, at)
+    }
+
+    /// 文件中的解压大小不合理时返回错误，不按它分配内存
+    #[test]
+    fn oversized_block() {
+        let (mut buf, at) = mdx_with_blocks();
+        let last = be_u64(&buf, at) as usize - 1;
+        let dsize_at = at + 32 + 16 * last + 8;
+        // 1TB，预先分配会失败
+        buf[dsize_at..dsize_at + 8].copy_from_slice(&(1_u64 << 40).to_be_bytes());
+
+        let mdx = Mdx::parse(&buf).unwrap();
+        assert!(matches!(mdx.record(mdx.len() - 1), Err(Error::Format(_))));
+
+        let mut reader = MdxReader::new(Cursor::new(&buf)).unwrap();
+        let result: Result<Vec<_>> = reader.records().collect();
+        assert!(matches!(result, Err(Error::Format(_))));
+
+        let report = crate::inspect::inspect(&buf, false, true);
+        let message = format!("record block {last}: ");
+        assert!(
+            report
+                .problems
+                .iter()
+                .any(|p| p.message.starts_with(&message))
+        );
+    }
+
+    /// 截断的文件和溢出的大小都记录为问题，inspect不会panic
+    #[test]
+    fn inspect_broken_headers() {
+        let entries =
+            [("apple", "苹果"), ("pear", "梨")].map(|(k, v)| (k.to_string(), v.to_string()));
+        let mut small = vec![];
+        MdxWriter::default().write(&entries, &mut small).unwrap();
+        for len in 0..small.len() {
+            let report = crate::inspect::inspect(&small[..len], false, true);
+            assert!(!report.problems.is_empty(), "truncated to {len} bytes");
+        }
+
+        let (buf, at) = mdx_with_blocks();
+
+        // block数乘以每项的长度溢出
+        let mut overflow = buf.clone();
+        overflow[at..at + 8].copy_from_slice(&(u64::MAX / 8).to_be_bytes());
+        let report = crate::inspect::inspect(&overflow, false, true);
+        assert!(report.problems[0].message.contains("record block info"));
+
+        // 两个block的解压大小之和溢出
+        let mut overflow = buf.clone();
+        for i in 0..2 {
+            let dsize_at = at + 32 + 16 * i + 8;
+            overflow[dsize_at..dsize_at + 8].copy_from_slice(&u64::MAX.to_be_bytes());
+        }
+        let report = crate::inspect::inspect(&overflow, false, true);
+        assert!(
+            report
+                .problems
+                .iter()
+                .any(|p| p.message == "record block sizes overflow")
+        );
+    }
+
+    /// 流式读取时block大小之和溢出返回错误
+    #[test]
+    fn reader_overflowing_blocks() {
+        let (mut buf, at) = mdx_with_blocks();
+        for i in 0..2 {
+            let dsize_at = at + 32 + 16 * i + 8;
+            buf[dsize_at..dsize_at + 8].copy_from_slice(&u64::MAX.to_be_bytes());
+        }
+        let result = MdxReader::new(Cursor::new(&buf));
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+}
@@ -1,14 +1,44 @@
 // 光标默认可输入
 $(document).ready(function (e) {
         $('#word').focus();
+        // /word/{headword} 页面的结果已经由服务端渲染
+        let word = wordFromPath(location.pathname);
+        if (word) {
+            history.replaceState({'word': word}, '', location.href);
+            queryNeighbours(word);
+        }
+        queryRecent();
     }
 );
 
-// 查询mdx
-function queryMdx(word) {
+// '/word/apple' -> 'apple'
+function wordFromPath(path) {
+    if (!path.startsWith('/word/')) {
+        return null;
+    }
+    return decodeURIComponent(path.slice('/word/'.length));
+}
+
+// 浏览器前进后退时重新查询
+window.addEventListener('popstate', function (e) {
+    let word = e.state && e.state.word;
+    if (word) {
+        $('#word').val(word);
+        queryMdx(word);
+    } else {
+        location.reload();
+    }
+});
+
+// 查询mdx, 并把查询词写入地址栏 /word/{word}
+function queryMdx(word, pushHistory) {
     $('#mdx-resp').html('查询中...');
+    if (pushHistory) {
+        history.pushState({'word': word}, '', '/word/' + encodeURIComponent(word));
+    }
+    document.title = word + ' - Moe词典';
     $.ajax({
-        url: './query',
+        url: '/query',
         type: 'POST',
         data: {'word': word},
         dataType: 'html',
@@ -18,8 +48,49 @@ function queryMdx(word) {
             } else {
                 $('#mdx-resp').hide();
             }
+            queryRecent();
         }
     });
+    queryNeighbours(word);
+}
+
+// 词典顺序中前后的词条
+function queryNeighbours(word) {
+    $.ajax({
+        url: '/neighbours',
+        type: 'GET',
+        data: {'word': word, 'n': 10},
+        dataType: 'json',
+        success: function (data) {
+            let list = $('<ul></ul>');
+            data.before.forEach(w => list.append(neighbourItem(w)));
+            list.append(neighbourItem(data.headword).addClass('current'));
+            data.after.forEach(w => list.append(neighbourItem(w)));
+            $('#neighbours').empty().append($('<h4></h4>').text(data.dictionary)).append(list);
+        },
+        error: function () {
+            $('#neighbours').empty();
+        }
+    });
+}
+
+// 最近查询过的词
+function queryRecent() {
+    $.ajax({
+        url: '/history/recent',
+        type: 'GET',
+        data: {'limit': 10},
+        dataType: 'json',
+        success: function (words) {
+            $('#recent').empty();
+            words.forEach(w => $('#recent').append(
+                $('<a></a>').attr('href', '/word/' + encodeURIComponent(w)).text(w)));
+        }
+    });
+}
+
+function neighbourItem(word) {
+    return $('<li></li>').append($('<a></a>').attr('href', '/word/' + encodeURIComponent(word)).text(word));
 }
 
 function postQuery() {
@@ -27,7 +98,7 @@ function postQuery() {
     if (!validInput(word)) {
         return;
     }
-    queryMdx(word);
+    queryMdx(word, true);
 }
 
 // 特殊字符不查询
@@ -46,12 +117,20 @@ $(document).keydown(function (e) {
     }
 });
 
+// 播放释义中的发音
+$(document).on('click', 'a[data-sound]', function (e) {
+    e.preventDefault();
+    e.stopImmediatePropagation();
+    new Audio($(this).attr('href')).play();
+});
+
 // 监听牛津8解释页面的外部单词链接
 $(document).on('click', 'a', function (e) {
     console.log($(this).attr('href'));
     let href = $(this).attr('href');// '/cool'
-    if (href.startsWith('/') && !href.startsWith('/#')) {
-        $('#word').val(href.slice(1)) // 'cool'
+    if (href && href.startsWith('/') && !href.startsWith('/#') && !href.startsWith('/resource/')) {
+        // '/word/cool' 或 '/cool'
+        $('#word').val(wordFromPath(href) || decodeURIComponent(href.slice(1))) // 'cool'
         postQuery();
         e.preventDefault()
     }
@@ -70,7 +149,7 @@ $(window).bind('keyup keydown', function (e) {
 // 试试手气按钮
 $(document).on('click', '#lucky-btn', function (e) {
     $.ajax({
-        url: './lucky',
+        url: '/lucky',
         type: 'GET',
         dataType: 'html',
         success: function (data) {
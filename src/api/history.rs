@@ -0,0 +1,169 @@
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{FromRequestParts, Query},
+    http::{header, request::Parts},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde_derive::{Deserialize, Serialize};
+
+use mdict_rs::dictionary::Dictionary;
+use mdict_rs::history::{self, HistoryFilter, HistoryPage, HistoryStats};
+
+use super::ApiError;
+
+/// 用户名的最大长度
+const MAX_USER_LEN: usize = 64;
+
+/// 查询历史所属的用户，来自 X-User 请求头或 user 参数，都没有时为匿名用户(空字符串)
+pub(crate) struct HistoryUser(pub String);
+
+#[derive(Deserialize)]
+struct UserParam {
+    user: Option<String>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for HistoryUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user = match parts.headers.get("x-user") {
+            Some(value) => value
+                .to_str()
+                .map_err(|_| ApiError::BadRequest("invalid X-User header".to_string()))?
+                .to_string(),
+            None => Query::<UserParam>::try_from_uri(&parts.uri)
+                .ok()
+                .and_then(|Query(param)| param.user)
+                .unwrap_or_default(),
+        };
+        let user = user.trim();
+        if user.chars().count() > MAX_USER_LEN || user.chars().any(char::is_control) {
+            return Err(ApiError::BadRequest(format!(
+                "user must be at most {MAX_USER_LEN} characters without control characters"
+            )));
+        }
+        Ok(HistoryUser(user.to_string()))
+    }
+}
+
+/// 查询历史，挂载在 /history 下
+pub(crate) fn routes() -> Router<Vec<Arc<dyn Dictionary>>> {
+    Router::new()
+        .route("/", get(handle_list).delete(handle_clear))
+        .route("/stats", get(handle_stats))
+        .route("/recent", get(handle_recent))
+        .route("/export", get(handle_export))
+}
+
+/// 各个接口共用的参数，过滤条件为 word dict hit since until(unix时间戳，秒)
+/// 都只包括当前用户的历史
+#[derive(Deserialize, Debug)]
+pub struct HistoryParams {
+    word: Option<String>,
+    dict: Option<String>,
+    hit: Option<bool>,
+    since: Option<i64>,
+    until: Option<i64>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    // 导出格式 json(默认) csv
+    format: Option<String>,
+}
+
+impl HistoryParams {
+    fn filter(&self, user: HistoryUser) -> HistoryFilter {
+        HistoryFilter {
+            user: user.0,
+            word: self.word.as_deref().map(str::trim).map(str::to_string),
+            dict: self.dict.clone(),
+            hit: self.hit,
+            since: self.since,
+            until: self.until,
+        }
+    }
+
+    fn limit(&self, default: usize) -> usize {
+        self.limit.unwrap_or(default).min(500)
+    }
+}
+
+/// GET /history?dict=牛津高阶8&hit=false&offset=0&limit=50，按时间倒序
+async fn handle_list(
+    user: HistoryUser,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<HistoryPage>, ApiError> {
+    let page = history::list(
+        &params.filter(user),
+        params.offset.unwrap_or(0),
+        params.limit(50),
+    )?;
+    Ok(Json(page))
+}
+
+/// GET /history/stats?since=1700000000&limit=20，limit为高频词的数量
+async fn handle_stats(
+    user: HistoryUser,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<HistoryStats>, ApiError> {
+    Ok(Json(history::stats(
+        &params.filter(user),
+        params.limit(20),
+    )?))
+}
+
+/// GET /history/recent?limit=10，最近查询过的词，页面上显示
+async fn handle_recent(
+    HistoryUser(user): HistoryUser,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<Vec<String>>, ApiError> {
+    Ok(Json(history::recent(&user, params.limit(10))?))
+}
+
+#[derive(Serialize)]
+struct Cleared {
+    deleted: usize,
+}
+
+/// DELETE /history?until=1700000000，删除过滤条件下的历史，没有条件时全部删除
+async fn handle_clear(
+    user: HistoryUser,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<Cleared>, ApiError> {
+    let deleted = history::clear(&params.filter(user))?;
+    Ok(Json(Cleared { deleted }))
+}
+
+/// GET /history/export?format=csv，按时间顺序导出为附件
+async fn handle_export(
+    user: HistoryUser,
+    Query(params): Query<HistoryParams>,
+) -> Result<Response, ApiError> {
+    let entries = history::export(&params.filter(user))?;
+    let (content_type, file, body) = match params.format.as_deref().unwrap_or("json") {
+        f if f.eq_ignore_ascii_case("csv") => (
+            "text/csv; charset=utf-8",
+            "history.csv",
+            history::to_csv(&entries),
+        ),
+        f if f.eq_ignore_ascii_case("json") => (
+            "application/json",
+            "history.json",
+            serde_json::to_string_pretty(&entries).map_err(anyhow::Error::from)?,
+        ),
+        f => return Err(ApiError::BadRequest(format!("unknown format: {f}"))),
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(r#"attachment; filename="{file}""#),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
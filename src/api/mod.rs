@@ -0,0 +1,165 @@
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde_derive::{Deserialize, Serialize};
+use tracing::error;
+
+use mdict_rs::dictionary::{Dictionary, find_dictionary};
+use mdict_rs::history::record_lookup;
+use mdict_rs::query::{LookupResult, TextFormat, lookup_all, render_result};
+
+pub(crate) mod history;
+pub(crate) mod vocabulary;
+
+use history::HistoryUser;
+
+/// 查询词的最大长度
+const MAX_WORD_LEN: usize = 256;
+
+/// 版本化的json api，挂载在 /api/v1 下
+pub(crate) fn routes() -> Router<Vec<Arc<dyn Dictionary>>> {
+    Router::new().route("/lookup", get(handle_lookup))
+}
+
+/// api错误，统一返回 {"error": "..."} 和对应的状态码
+#[derive(Debug)]
+pub(crate) enum ApiError {
+    BadRequest(String),
+    NotFound(String),
+    Internal(anyhow::Error),
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+            ApiError::Internal(e) => {
+                error!("api internal error: {:?}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "internal server error".to_string(),
+                )
+            }
+        };
+        (status, Json(ErrorBody { error: message })).into_response()
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        ApiError::Internal(e)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LookupParams {
+    word: Option<String>,
+    dict: Option<String>,
+    // json(默认) text markdown
+    format: Option<String>,
+}
+
+/// GET /api/v1/lookup?word=apple&dict=牛津高阶8&format=text
+/// 返回格式由format参数决定，没有format时根据Accept头: text/plain text/markdown，其他返回json
+async fn handle_lookup(
+    State(dicts): State<Vec<Arc<dyn Dictionary>>>,
+    HistoryUser(user): HistoryUser,
+    Query(params): Query<LookupParams>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let format = response_format(params.format.as_deref(), &headers)?;
+    let word = valid_word(params.word.as_deref())?;
+    if let Some(dict) = params.dict.as_deref() {
+        valid_dict(&dicts, dict)?;
+    }
+    let results = lookup_all(&dicts, word, params.dict.as_deref())?;
+    record_lookup(&user, word, &results);
+    if results.is_empty() {
+        return Err(ApiError::NotFound(format!("{word} not found")));
+    }
+    Ok(render_results(&results, format))
+}
+
+/// 查询结果按格式输出，多个词典的文本之间空一行
+fn render_results(results: &[LookupResult], format: Option<TextFormat>) -> Response {
+    let Some(format) = format else {
+        return Json(results).into_response();
+    };
+    let content_type = match format {
+        TextFormat::Text => "text/plain; charset=utf-8",
+        TextFormat::Markdown => "text/markdown; charset=utf-8",
+    };
+    let body = results
+        .iter()
+        .map(|r| render_result(r, format))
+        .collect::<Vec<_>>()
+        .join("\n");
+    ([(header::CONTENT_TYPE, content_type)], body).into_response()
+}
+
+/// 输出格式，None表示json
+fn response_format(
+    format: Option<&str>,
+    headers: &HeaderMap,
+) -> Result<Option<TextFormat>, ApiError> {
+    if let Some(format) = format {
+        if format.eq_ignore_ascii_case("json") || format.eq_ignore_ascii_case("html") {
+            return Ok(None);
+        }
+        return TextFormat::parse(format)
+            .map(Some)
+            .ok_or_else(|| ApiError::BadRequest(format!("unknown format: {format}")));
+    }
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default();
+    let format = if accept.contains("text/markdown") {
+        Some(TextFormat::Markdown)
+    } else if accept.contains("text/plain") {
+        Some(TextFormat::Text)
+    } else {
+        None
+    };
+    Ok(format)
+}
+
+/// 检查查询词: 不能为空，不能过长，不能包含控制字符
+pub(crate) fn valid_word(word: Option<&str>) -> Result<&str, ApiError> {
+    let word = word.map(str::trim).unwrap_or_default();
+    if word.is_empty() {
+        return Err(ApiError::BadRequest("word is required".to_string()));
+    }
+    if word.chars().count() > MAX_WORD_LEN {
+        return Err(ApiError::BadRequest(format!(
+            "word is longer than {MAX_WORD_LEN} characters"
+        )));
+    }
+    if word.chars().any(char::is_control) {
+        return Err(ApiError::BadRequest(
+            "word contains control characters".to_string(),
+        ));
+    }
+    Ok(word)
+}
+
+/// 检查词典名是否已配置
+pub(crate) fn valid_dict(dicts: &[Arc<dyn Dictionary>], dict: &str) -> Result<(), ApiError> {
+    if find_dictionary(dicts, dict).is_some() {
+        Ok(())
+    } else {
+        Err(ApiError::BadRequest(format!("unknown dictionary: {dict}")))
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+};
+use serde_derive::{Deserialize, Serialize};
+
+use mdict_rs::dictionary::Dictionary;
+use mdict_rs::query::{LookupResult, lookup_all};
+use mdict_rs::vocabulary::{self, Card, MAX_GRADE, WordList};
+
+use super::{ApiError, valid_dict, valid_word};
+
+/// 生词本名称的最大长度
+const MAX_LIST_NAME_LEN: usize = 64;
+
+/// 生词本和复习，挂载在 /vocabulary 下
+pub(crate) fn routes() -> Router<Vec<Arc<dyn Dictionary>>> {
+    Router::new()
+        .route("/lists", get(handle_lists))
+        .route("/lists/{list}", delete(handle_delete_list))
+        .route("/lists/{list}/words", get(handle_words).post(handle_add))
+        .route("/lists/{list}/words/{word}", delete(handle_remove))
+        .route("/due", get(handle_due))
+        .route("/cards/{id}/review", post(handle_review))
+}
+
+/// 检查生词本名称: 不能为空，不能过长，不能包含控制字符
+fn valid_list(list: &str) -> Result<&str, ApiError> {
+    let list = list.trim();
+    if list.is_empty()
+        || list.chars().count() > MAX_LIST_NAME_LEN
+        || list.chars().any(char::is_control)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "list name must be 1 to {MAX_LIST_NAME_LEN} characters"
+        )));
+    }
+    Ok(list)
+}
+
+/// GET /vocabulary/lists
+async fn handle_lists() -> Result<Json<Vec<WordList>>, ApiError> {
+    Ok(Json(vocabulary::lists()?))
+}
+
+/// DELETE /vocabulary/lists/{list}，同时删除其中的单词
+async fn handle_delete_list(Path(list): Path<String>) -> Result<StatusCode, ApiError> {
+    if vocabulary::delete_list(valid_list(&list)?)? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!("{list} not found")))
+    }
+}
+
+/// GET /vocabulary/lists/{list}/words，单词和复习状态
+async fn handle_words(Path(list): Path<String>) -> Result<Json<Vec<Card>>, ApiError> {
+    vocabulary::cards(valid_list(&list)?)?
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("{list} not found")))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AddWord {
+    word: String,
+    // 只复习这个词典的释义
+    dict: Option<String>,
+}
+
+/// POST /vocabulary/lists/{list}/words {"word": "apple", "dict": "牛津高阶8"}
+/// 单词必须能查到，保存查到的词头，新加入返回201，已经在生词本中返回200
+async fn handle_add(
+    State(dicts): State<Vec<Arc<dyn Dictionary>>>,
+    Path(list): Path<String>,
+    Json(body): Json<AddWord>,
+) -> Result<Response, ApiError> {
+    let list = valid_list(&list)?;
+    let word = valid_word(Some(&body.word))?;
+    if let Some(dict) = body.dict.as_deref() {
+        valid_dict(&dicts, dict)?;
+    }
+    let results = lookup_all(&dicts, word, body.dict.as_deref())?;
+    let Some(result) = results.first() else {
+        return Err(ApiError::NotFound(format!("{word} not found")));
+    };
+    let (card, added) = vocabulary::add_word(list, &result.headword, body.dict.as_deref())?;
+    let status = if added {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+    Ok((status, Json(card)).into_response())
+}
+
+/// DELETE /vocabulary/lists/{list}/words/{word}
+async fn handle_remove(Path((list, word)): Path<(String, String)>) -> Result<StatusCode, ApiError> {
+    if vocabulary::remove_word(valid_list(&list)?, word.trim())? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!("{word} not found in {list}")))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DueParams {
+    list: Option<String>,
+    limit: Option<usize>,
+}
+
+/// 需要复习的单词和它的释义
+#[derive(Serialize)]
+struct DueCard {
+    #[serde(flatten)]
+    card: Card,
+    definitions: Vec<LookupResult>,
+}
+
+/// GET /vocabulary/due?list=toefl&limit=20，到期的单词，释义从词典索引中查询
+async fn handle_due(
+    State(dicts): State<Vec<Arc<dyn Dictionary>>>,
+    Query(params): Query<DueParams>,
+) -> Result<Json<Vec<DueCard>>, ApiError> {
+    let list = params.list.as_deref().map(valid_list).transpose()?;
+    let limit = params.limit.unwrap_or(20).min(200);
+    let mut due = vec![];
+    for card in vocabulary::due_cards(list, limit)? {
+        let definitions = lookup_all(&dicts, &card.word, card.dictionary.as_deref())?;
+        due.push(DueCard { card, definitions });
+    }
+    Ok(Json(due))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ReviewGrade {
+    // 0-5: 0完全不记得，3想起来但很费力，5毫不犹豫
+    grade: u8,
+}
+
+/// POST /vocabulary/cards/{id}/review {"grade": 4}，返回更新后的复习状态
+async fn handle_review(
+    Path(id): Path<i64>,
+    Json(body): Json<ReviewGrade>,
+) -> Result<Json<Card>, ApiError> {
+    if body.grade > MAX_GRADE {
+        return Err(ApiError::BadRequest(format!(
+            "grade must be between 0 and {MAX_GRADE}"
+        )));
+    }
+    vocabulary::review(id, body.grade)?
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("card {id} not found")))
+}
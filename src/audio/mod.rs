@@ -0,0 +1,92 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, LazyLock, Mutex};
+
+mod speex;
+
+/// 最多缓存的转码结果数量
+const CACHE_CAPACITY: usize = 512;
+
+/// 转码结果缓存，key为 "词典/资源路径"，超过容量时淘汰最早加入的
+static CACHE: LazyLock<Mutex<AudioCache>> = LazyLock::new(|| Mutex::new(AudioCache::default()));
+
+#[derive(Default)]
+struct AudioCache {
+    entries: HashMap<String, Arc<Vec<u8>>>,
+    order: VecDeque<String>,
+}
+
+/// 浏览器可以播放的音频
+pub struct Audio {
+    pub content_type: String,
+    pub data: Arc<Vec<u8>>,
+}
+
+/// 浏览器不能播放的格式(speex)转码为wav，其他格式原样返回
+/// load只在缓存未命中时调用，返回资源文件的原始bytes
+pub fn playable(
+    dict: &str,
+    path: &str,
+    load: impl FnOnce() -> anyhow::Result<Option<Vec<u8>>>,
+) -> anyhow::Result<Option<Audio>> {
+    if !path.to_lowercase().ends_with(".spx") {
+        return Ok(load()?.map(|data| Audio {
+            content_type: mime_guess::from_path(path)
+                .first_or_octet_stream()
+                .to_string(),
+            data: Arc::new(data),
+        }));
+    }
+
+    let key = format!("{dict}/{path}");
+    if let Some(data) = CACHE.lock().unwrap().entries.get(&key) {
+        return Ok(Some(Audio {
+            content_type: "audio/wav".to_string(),
+            data: data.clone(),
+        }));
+    }
+
+    let Some(spx) = load()? else {
+        return Ok(None);
+    };
+    let pcm = speex::decode(&spx)?;
+    let data = Arc::new(wav(pcm.rate, &pcm.samples));
+
+    let mut cache = CACHE.lock().unwrap();
+    if cache.order.len() >= CACHE_CAPACITY
+        && let Some(oldest) = cache.order.pop_front()
+    {
+        cache.entries.remove(&oldest);
+    }
+    if cache.entries.insert(key.clone(), data.clone()).is_none() {
+        cache.order.push_back(key);
+    }
+    Ok(Some(Audio {
+        content_type: "audio/wav".to_string(),
+        data,
+    }))
+}
+
+/// 16bit单声道pcm写成wav文件
+fn wav(rate: u32, samples: &[i16]) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+    let mut buf = Vec::with_capacity(44 + data_len as usize);
+    buf.extend_from_slice(b"RIFF");
+    buf.extend_from_slice(&(36 + data_len).to_le_bytes());
+    buf.extend_from_slice(b"WAVE");
+    buf.extend_from_slice(b"fmt ");
+    buf.extend_from_slice(&16u32.to_le_bytes());
+    // pcm, 单声道
+    buf.extend_from_slice(&1u16.to_le_bytes());
+    buf.extend_from_slice(&1u16.to_le_bytes());
+    buf.extend_from_slice(&rate.to_le_bytes());
+    // byte rate, block align, bits per sample
+    buf.extend_from_slice(&(rate * 2).to_le_bytes());
+    buf.extend_from_slice(&2u16.to_le_bytes());
+    buf.extend_from_slice(&16u16.to_le_bytes());
+    buf.extend_from_slice(b"data");
+    buf.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        buf.extend_from_slice(&sample.to_le_bytes());
+    }
+    buf
+}
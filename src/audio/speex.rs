@@ -0,0 +1,182 @@
+use std::ffi::{c_char, c_int, c_void};
+use std::io::Cursor;
+
+use anyhow::{Context, bail};
+use ogg::PacketReader;
+
+/// libspeex 的 SpeexBits，字段和 speex_bits.h 一致
+#[repr(C)]
+struct SpeexBits {
+    chars: *mut c_char,
+    nb_bits: c_int,
+    char_ptr: c_int,
+    bit_ptr: c_int,
+    owner: c_int,
+    overflow: c_int,
+    buf_size: c_int,
+    reserved1: c_int,
+    reserved2: *mut c_void,
+}
+
+const SPEEX_SET_ENH: c_int = 0;
+const SPEEX_GET_FRAME_SIZE: c_int = 3;
+const SPEEX_NB_MODES: i32 = 3;
+
+unsafe extern "C" {
+    fn speex_lib_get_mode(mode: c_int) -> *const c_void;
+    fn speex_decoder_init(mode: *const c_void) -> *mut c_void;
+    fn speex_decoder_destroy(state: *mut c_void);
+    fn speex_decoder_ctl(state: *mut c_void, request: c_int, ptr: *mut c_void) -> c_int;
+    fn speex_decode_int(state: *mut c_void, bits: *mut SpeexBits, out: *mut i16) -> c_int;
+    fn speex_bits_init(bits: *mut SpeexBits);
+    fn speex_bits_read_from(bits: *mut SpeexBits, bytes: *const c_char, len: c_int);
+    fn speex_bits_remaining(bits: *mut SpeexBits) -> c_int;
+    fn speex_bits_destroy(bits: *mut SpeexBits);
+}
+
+/// ogg中第一个packet的speex header，80 bytes，数字都是小端
+struct SpeexHeader {
+    rate: u32,
+    mode: i32,
+    frames_per_packet: u32,
+}
+
+fn parse_speex_header(packet: &[u8]) -> anyhow::Result<SpeexHeader> {
+    if packet.len() < 80 || &packet[0..8] != b"Speex   " {
+        bail!("not a speex stream");
+    }
+    let field = |offset: usize| {
+        i32::from_le_bytes([
+            packet[offset],
+            packet[offset + 1],
+            packet[offset + 2],
+            packet[offset + 3],
+        ])
+    };
+    // "Speex   "(8) + version(20) + version_id + header_size 之后依次是 rate mode ... frames_per_packet
+    let header = SpeexHeader {
+        rate: field(36) as u32,
+        mode: field(40),
+        frames_per_packet: field(64).max(1) as u32,
+    };
+    if !(0..SPEEX_NB_MODES).contains(&header.mode) {
+        bail!("unknown speex mode: {}", header.mode);
+    }
+    Ok(header)
+}
+
+/// 解码后的pcm，16bit单声道
+pub struct Pcm {
+    pub rate: u32,
+    pub samples: Vec<i16>,
+}
+
+/// 解码ogg封装的speex(.spx)
+pub fn decode(data: &[u8]) -> anyhow::Result<Pcm> {
+    let mut reader = PacketReader::new(Cursor::new(data));
+    let first = reader
+        .read_packet()?
+        .context("empty ogg stream")?;
+    let header = parse_speex_header(&first.data)?;
+
+    let mut decoder = Decoder::new(header.mode)?;
+    let mut samples = vec![];
+    // 第二个packet是comment，跳过
+    let mut index = 1;
+    while let Some(packet) = reader.read_packet()? {
+        index += 1;
+        if index <= 2 {
+            continue;
+        }
+        decoder.decode_packet(&packet.data, header.frames_per_packet, &mut samples)?;
+    }
+    Ok(Pcm {
+        rate: header.rate,
+        samples,
+    })
+}
+
+/// libspeex 解码器状态，drop时释放
+struct Decoder {
+    state: *mut c_void,
+    bits: Box<SpeexBits>,
+    frame_size: usize,
+}
+
+impl Decoder {
+    fn new(mode: i32) -> anyhow::Result<Decoder> {
+        // SAFETY: mode已经检查在 [0, SPEEX_NB_MODES) 范围内，bits由speex_bits_init初始化
+        unsafe {
+            let state = speex_decoder_init(speex_lib_get_mode(mode));
+            if state.is_null() {
+                bail!("speex decoder init failed");
+            }
+            let mut frame_size: c_int = 0;
+            speex_decoder_ctl(
+                state,
+                SPEEX_GET_FRAME_SIZE,
+                &mut frame_size as *mut c_int as *mut c_void,
+            );
+            let mut enh: c_int = 1;
+            speex_decoder_ctl(state, SPEEX_SET_ENH, &mut enh as *mut c_int as *mut c_void);
+
+            let mut bits = Box::new(SpeexBits {
+                chars: std::ptr::null_mut(),
+                nb_bits: 0,
+                char_ptr: 0,
+                bit_ptr: 0,
+                owner: 0,
+                overflow: 0,
+                buf_size: 0,
+                reserved1: 0,
+                reserved2: std::ptr::null_mut(),
+            });
+            speex_bits_init(bits.as_mut());
+            Ok(Decoder {
+                state,
+                bits,
+                frame_size: frame_size as usize,
+            })
+        }
+    }
+
+    fn decode_packet(
+        &mut self,
+        packet: &[u8],
+        frames_per_packet: u32,
+        samples: &mut Vec<i16>,
+    ) -> anyhow::Result<()> {
+        let mut frame = vec![0i16; self.frame_size];
+        // SAFETY: packet在调用期间有效，frame的长度是解码器的frame_size
+        unsafe {
+            speex_bits_read_from(
+                self.bits.as_mut(),
+                packet.as_ptr() as *const c_char,
+                packet.len() as c_int,
+            );
+            for _ in 0..frames_per_packet {
+                match speex_decode_int(self.state, self.bits.as_mut(), frame.as_mut_ptr()) {
+                    // 流结束
+                    -1 => break,
+                    -2 => bail!("corrupted speex stream"),
+                    _ => {}
+                }
+                if speex_bits_remaining(self.bits.as_mut()) < 0 {
+                    bail!("speex decoding overflow");
+                }
+                samples.extend_from_slice(&frame);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Decoder {
+    fn drop(&mut self) {
+        // SAFETY: state和bits由new初始化，只释放一次
+        unsafe {
+            speex_bits_destroy(self.bits.as_mut());
+            speex_decoder_destroy(self.state);
+        }
+    }
+}
@@ -0,0 +1,501 @@
+use std::fs;
+use std::io::{BufWriter, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use clap::{Args, Parser, Subcommand};
+
+use mdict_rs::config::{MDX_FILES, dict_name, mdd_files};
+use mdict_rs::convert::epub::write_epub;
+use mdict_rs::convert::stardict::write_stardict;
+use mdict_rs::convert::yomitan::write_yomitan;
+use mdict_rs::convert::{ConvertFormat, Entries, Metadata, mdd_resources};
+use mdict_rs::dictionary::{Dictionary, MmapMdx, open_dictionaries};
+use mdict_rs::export::{ExportFormat, collect_resources, export_mdx, extract_mdd, parse_source};
+use mdict_rs::indexing::indexing;
+use mdict_rs::mdict::MdxReader;
+use mdict_rs::mdict::inspect::{Blocks, inspect};
+use mdict_rs::mdict::mdd::Mdd;
+use mdict_rs::mdict::writer::{Compression, MdxWriter};
+use mdict_rs::query::{TextFormat, lookup_all, render_result};
+
+/// 命令行查词: mdict apple
+#[derive(Parser)]
+#[command(name = "mdict", version, about = "Look up words in mdict dictionaries")]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    lookup: LookupArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Look up a word (default command)
+    Lookup(LookupArgs),
+    /// Print the header and block structure of an mdx/mdd file
+    Info { file: PathBuf },
+    /// Decode every block of an mdx/mdd file and check sizes and checksums
+    Verify { file: PathBuf },
+    /// Export every entry of an mdx file, and optionally its mdd resources
+    Export(ExportArgs),
+    /// Build an mdx file from mdict source text
+    Build(BuildArgs),
+    /// Pack a directory of resources (images, audio, css) into an mdd file
+    Pack(PackArgs),
+    /// Convert an mdx file to StarDict, a Yomitan dictionary or an EPUB/Kindle dictionary
+    Convert(ConvertArgs),
+}
+
+#[derive(Args)]
+struct ConvertArgs {
+    /// The mdx file to convert, mdd files next to it provide the resources
+    file: PathBuf,
+    /// Output format: stardict, yomitan or epub
+    #[arg(short, long, value_parser = parse_convert_format)]
+    format: ConvertFormat,
+    /// The .ifo, .zip or .epub file to write, StarDict resources go to res/ next to it
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Dictionary title, the mdx file name by default
+    #[arg(long)]
+    title: Option<String>,
+    #[arg(long, default_value = "")]
+    description: String,
+    /// Language of the headwords
+    #[arg(long, default_value = "en")]
+    source_language: String,
+    /// Language of the definitions
+    #[arg(long, default_value = "en")]
+    target_language: String,
+}
+
+#[derive(Args)]
+struct PackArgs {
+    /// Directory whose files become the mdd resources, paths relative to it
+    dir: PathBuf,
+    /// The mdd file to write, usually next to the mdx with the same name
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Block compression: zlib, lzo or none
+    #[arg(short, long, default_value = "zlib", value_parser = parse_compression)]
+    compression: Compression,
+    /// Encrypt the key block info (Encrypted="2")
+    #[arg(long)]
+    encrypt: bool,
+}
+
+#[derive(Args)]
+struct BuildArgs {
+    /// Source text: headword, definition lines, then a `</>` line for every entry
+    source: PathBuf,
+    /// The mdx file to write
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Dictionary title, the source file name by default
+    #[arg(long)]
+    title: Option<String>,
+    #[arg(long, default_value = "")]
+    description: String,
+    /// Block compression: zlib, lzo or none
+    #[arg(short, long, default_value = "zlib", value_parser = parse_compression)]
+    compression: Compression,
+    /// Encrypt the key block info (Encrypted="2")
+    #[arg(long)]
+    encrypt: bool,
+    /// Encoding of headwords and definitions
+    #[arg(long, default_value = "UTF-8")]
+    encoding: String,
+}
+
+#[derive(Args)]
+struct ExportArgs {
+    /// The mdx file to export
+    file: PathBuf,
+    /// Output format: source (mdict source text), tsv or jsonl
+    #[arg(short, long, default_value = "source", value_parser = parse_export_format)]
+    format: ExportFormat,
+    /// Output file, stdout when omitted
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Render definitions as plain text instead of html
+    #[arg(long)]
+    text: bool,
+    /// Render definitions as markdown instead of html
+    #[arg(long, conflicts_with = "text")]
+    markdown: bool,
+    /// Extract the resources of the mdd files next to the mdx into this directory
+    #[arg(long)]
+    resources: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct LookupArgs {
+    /// Word to look up
+    word: Option<String>,
+    /// Only look up in this dictionary (file stem of a configured mdx)
+    #[arg(short, long)]
+    dict: Option<String>,
+    /// Read this mdx file directly instead of the configured dictionaries' index
+    #[arg(short, long, conflicts_with = "dict")]
+    mdx: Option<PathBuf>,
+    /// Print the results of every dictionary, not only the first one
+    #[arg(short, long)]
+    all: bool,
+    /// Print results as JSON
+    #[arg(long, conflicts_with = "raw")]
+    json: bool,
+    /// Print the definition html as is
+    #[arg(long)]
+    raw: bool,
+    /// Print markdown instead of plain text
+    #[arg(long, conflicts_with_all = ["json", "raw"])]
+    markdown: bool,
+    /// Disable colored output
+    #[arg(long)]
+    no_color: bool,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Some(Command::Lookup(args)) => lookup(args),
+        Some(Command::Info { file }) => info(&file, false),
+        Some(Command::Verify { file }) => info(&file, true),
+        Some(Command::Export(args)) => export(args),
+        Some(Command::Build(args)) => build(args),
+        Some(Command::Pack(args)) => pack(args),
+        Some(Command::Convert(args)) => convert(args),
+        None => lookup(cli.lookup),
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("mdict: {e:#}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// 查词并输出，没有结果时返回false
+fn lookup(args: LookupArgs) -> anyhow::Result<bool> {
+    let Some(word) = args
+        .word
+        .as_deref()
+        .map(str::trim)
+        .filter(|w| !w.is_empty())
+    else {
+        anyhow::bail!("missing word, see `mdict --help`");
+    };
+
+    let dicts: Vec<Arc<dyn Dictionary>> = match &args.mdx {
+        // 不建索引，直接mmap读取mdx文件
+        Some(file) => vec![Arc::new(MmapMdx::open(file)?)],
+        None => {
+            if let Some(dict) = &args.dict
+                && !MDX_FILES.iter().any(|f| dict_name(f) == *dict)
+            {
+                anyhow::bail!("unknown dictionary: {dict}");
+            }
+            // 和web服务一样，缺少或过期的索引先建好
+            indexing(MDX_FILES, false)?;
+            open_dictionaries(MDX_FILES)
+        }
+    };
+    let mut results = lookup_all(&dicts, word, args.dict.as_deref())?;
+    if !args.all {
+        results.truncate(1);
+    }
+    if results.is_empty() {
+        eprintln!("{word}: not found");
+        return Ok(false);
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+        return Ok(true);
+    }
+    let color = !args.no_color
+        && !args.markdown
+        && std::env::var_os("NO_COLOR").is_none()
+        && std::io::stdout().is_terminal();
+    for (i, result) in results.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        if args.raw {
+            println!("{}", result.html);
+        } else if args.markdown {
+            print!("{}", render_result(result, TextFormat::Markdown));
+        } else if color {
+            print!("{}", colorize(&render_result(result, TextFormat::Text)));
+        } else {
+            print!("{}", render_result(result, TextFormat::Text));
+        }
+    }
+    Ok(true)
+}
+
+/// 输出文件结构，verify时解压所有block，有问题时返回false
+fn info(file: &Path, verify: bool) -> anyhow::Result<bool> {
+    let data = fs::read(file).map_err(|e| anyhow::anyhow!("read {}: {e}", file.display()))?;
+    let mdd = file
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("mdd"));
+    let report = inspect(&data, mdd, verify);
+
+    println!(
+        "file:            {} ({} bytes)",
+        file.display(),
+        report.file_size
+    );
+    println!("version:         {}", report.version);
+    println!("encoding:        {}", report.encoding);
+    println!("encrypted:       {}", report.encrypted);
+    println!("entries:         {}", report.entry_num);
+    if let Some(decoded) = report.decoded_entries {
+        println!("decoded entries: {decoded}");
+    }
+    println!("key block info:  {} bytes", report.key_block_info_size);
+    print_blocks("key blocks:     ", &report.key_blocks);
+    print_blocks("record blocks:  ", &report.record_blocks);
+    println!("header:");
+    for (name, value) in &report.attributes {
+        println!("  {name} = {value:?}");
+    }
+
+    if report.problems.is_empty() {
+        if verify {
+            println!("ok: all blocks decoded, sizes and checksums match");
+        }
+        return Ok(true);
+    }
+    println!("problems:");
+    for p in &report.problems {
+        println!("  at offset {:#x} ({}): {}", p.offset, p.offset, p.message);
+    }
+    Ok(false)
+}
+
+fn print_blocks(label: &str, blocks: &Blocks) {
+    let methods = |m: &std::collections::BTreeMap<&str, usize>| {
+        m.iter()
+            .map(|(name, n)| format!("{name}×{n}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    println!(
+        "{label} {} blocks, {} -> {} bytes, compression: {}, encryption: {}",
+        blocks.count,
+        blocks.compressed_size,
+        blocks.decompressed_size,
+        methods(&blocks.compression),
+        methods(&blocks.encryption)
+    );
+}
+
+fn parse_export_format(name: &str) -> Result<ExportFormat, String> {
+    ExportFormat::parse(name)
+        .ok_or_else(|| format!("unknown format {name}, use source, tsv or jsonl"))
+}
+
+/// 导出词条到文件或stdout，统计信息输出到stderr
+fn export(args: ExportArgs) -> anyhow::Result<bool> {
+    let mut mdx = MdxReader::open(&args.file)
+        .map_err(|e| anyhow::anyhow!("read {}: {e}", args.file.display()))?;
+    let text = if args.text {
+        Some(TextFormat::Text)
+    } else if args.markdown {
+        Some(TextFormat::Markdown)
+    } else {
+        None
+    };
+
+    let n = match &args.output {
+        Some(path) => {
+            let mut n = 0;
+            write_output(path, |out| {
+                n = export_mdx(&mut mdx, args.format, text, out)?;
+                Ok(())
+            })?;
+            n
+        }
+        None => export_mdx(
+            &mut mdx,
+            args.format,
+            text,
+            &mut BufWriter::new(std::io::stdout().lock()),
+        )?,
+    };
+    eprintln!("exported {n} entries");
+
+    if let Some(dir) = &args.resources {
+        for mdd_file in mdd_files(&args.file.to_string_lossy()) {
+            let mdd = Mdd::open(&mdd_file)
+                .map_err(|e| anyhow::anyhow!("read {}: {e}", mdd_file.display()))?;
+            let n = extract_mdd(&mdd, dir)?;
+            eprintln!("extracted {n} resources from {}", mdd_file.display());
+        }
+    }
+    Ok(true)
+}
+
+fn parse_compression(name: &str) -> Result<Compression, String> {
+    Compression::parse(name)
+        .ok_or_else(|| format!("unknown compression {name}, use zlib, lzo or none"))
+}
+
+/// 从源文件生成mdx
+fn build(args: BuildArgs) -> anyhow::Result<bool> {
+    let source = fs::read_to_string(&args.source)
+        .map_err(|e| anyhow::anyhow!("read {}: {e}", args.source.display()))?;
+    let entries = parse_source(&source);
+    let title = args
+        .title
+        .unwrap_or_else(|| dict_name(&args.source.to_string_lossy()));
+    let writer = MdxWriter {
+        title,
+        description: args.description,
+        encoding: args.encoding,
+        compression: args.compression,
+        encrypt_key_info: args.encrypt,
+        ..Default::default()
+    };
+    write_output(&args.output, |out| Ok(writer.write(&entries, out)?))?;
+    eprintln!(
+        "wrote {} entries to {}",
+        entries.len(),
+        args.output.display()
+    );
+    Ok(true)
+}
+
+/// 目录中的资源文件打包为mdd
+fn pack(args: PackArgs) -> anyhow::Result<bool> {
+    let resources = collect_resources(&args.dir)?;
+    let writer = MdxWriter {
+        compression: args.compression,
+        encrypt_key_info: args.encrypt,
+        ..Default::default()
+    };
+    write_output(&args.output, |out| Ok(writer.write_mdd(&resources, out)?))?;
+    eprintln!(
+        "packed {} resources into {}",
+        resources.len(),
+        args.output.display()
+    );
+    Ok(true)
+}
+
+/// 先写入同目录的临时文件，成功后再改名为output，失败时不留下不完整的文件
+fn write_output(
+    output: &Path,
+    write: impl FnOnce(&mut BufWriter<fs::File>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut tmp = output.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let file =
+        fs::File::create(&tmp).map_err(|e| anyhow::anyhow!("create {}: {e}", tmp.display()))?;
+    let mut out = BufWriter::new(file);
+    let result = write(&mut out)
+        .and_then(|_| Ok(out.into_inner().map_err(|e| e.into_error())?.sync_all()?))
+        .and_then(|_| Ok(fs::rename(&tmp, output)?));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result.map_err(|e| anyhow::anyhow!("write {}: {e}", output.display()))
+}
+
+fn parse_convert_format(name: &str) -> Result<ConvertFormat, String> {
+    ConvertFormat::parse(name)
+        .ok_or_else(|| format!("unknown format {name}, use stardict, yomitan or epub"))
+}
+
+/// mdx转换为其他词典格式
+fn convert(args: ConvertArgs) -> anyhow::Result<bool> {
+    let mut mdx = MdxReader::open(&args.file)
+        .map_err(|e| anyhow::anyhow!("read {}: {e}", args.file.display()))?;
+    let entries = Entries::from_mdx(&mut mdx)?;
+    let file = args.file.to_string_lossy();
+    let mdds = mdd_files(&file)
+        .iter()
+        .map(|f| Mdd::open(f).map_err(|e| anyhow::anyhow!("read {}: {e}", f.display())))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let metadata = Metadata {
+        title: args.title.unwrap_or_else(|| dict_name(&file)),
+        description: args.description,
+        source_language: args.source_language,
+        target_language: args.target_language,
+    };
+    match args.format {
+        ConvertFormat::StarDict => {
+            write_stardict(&entries, &metadata, &args.output)?;
+            let res = args.output.with_file_name("res");
+            for mdd in &mdds {
+                let n = extract_mdd(mdd, &res)?;
+                eprintln!("extracted {n} resources into {}", res.display());
+            }
+        }
+        ConvertFormat::Yomitan => {
+            write_output(&args.output, |out| write_yomitan(&entries, &metadata, out))?
+        }
+        ConvertFormat::Epub => {
+            let resources = mdd_resources(&mdds)?;
+            write_output(&args.output, |out| {
+                write_epub(&entries, &metadata, &resources, out)
+            })?
+        }
+    }
+    eprintln!(
+        "converted {} entries and {} alternative headwords to {}",
+        entries.items.len(),
+        entries.aliases.len(),
+        args.output.display()
+    );
+    Ok(true)
+}
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const CYAN: &str = "\x1b[36m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// 纯文本结果加上终端颜色: 词头加粗，词典名变暗，义项编号和例句着色
+fn colorize(text: &str) -> String {
+    let mut out = String::new();
+    for (i, line) in text.lines().enumerate() {
+        if i == 0 {
+            match line.rsplit_once("  [") {
+                Some((headword, dict)) => out.push_str(&format!(
+                    "{BOLD}{CYAN}{headword}{RESET}  {DIM}[{dict}{RESET}"
+                )),
+                None => out.push_str(&format!("{BOLD}{CYAN}{line}{RESET}")),
+            }
+        } else if line.trim_start().starts_with('»') {
+            out.push_str(&format!("{GREEN}{line}{RESET}"));
+        } else if let Some((number, rest)) = numbered(line) {
+            out.push_str(&format!("{BOLD}{YELLOW}{number}{RESET}{rest}"));
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// "  2. sense" -> ("  2.", " sense")
+fn numbered(line: &str) -> Option<(&str, &str)> {
+    let digits = line.trim_start();
+    let start = line.len() - digits.len();
+    let n = digits.chars().take_while(char::is_ascii_digit).count();
+    if n == 0 || !digits[n..].starts_with(". ") {
+        return None;
+    }
+    Some(line.split_at(start + n + 1))
+}
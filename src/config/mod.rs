@@ -1,18 +1,77 @@
 use std::collections::HashMap;
 use std::env;
-use std::path::PathBuf;
-use std::sync::LazyLock;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
 
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 use tracing::info;
 
+use crate::sanitize::TrustLevel;
+
+/// 词典文件，mdx、stardict的 .ifo 或 dsl(.dz)，按顺序显示查询结果
 pub const MDX_FILES: &[&str] = &[
     "./resources/mdx/en/牛津高阶8.mdx",
     "./resources/mdx/en/朗文当代4.mdx",
     "./resources/mdx/zh/汉语词典3.mdx",
 ];
 
+/// 查询历史的sqlite数据库
+pub const HISTORY_DB: &str = "./resources/history.db";
+
+/// 生词本和复习记录的sqlite数据库
+pub const VOCABULARY_DB: &str = "./resources/vocabulary.db";
+
+/// DICT协议(RFC 2229)服务的监听地址，GoldenDict、dict等客户端默认使用2628端口
+pub const DICT_SERVER_ADDR: &str = "0.0.0.0:2628";
+
+/// 词典自带的css和js，路径相对于mdd、mdx所在目录或static目录
+/// 没有配置的词典会自动查找同名的 {词典名}.css 和 {词典名}.js
+pub const DICT_ASSETS: &[(&str, &[&str])] = &[
+    ("牛津高阶8", &["O8C.css"]),
+    ("朗文当代4", &["LSC4.css"]),
+    ("汉语词典3", &["hycd_3rd.css", "hycd_3rd.js"]),
+];
+
+/// 词典的信任级别，未配置的词典为 Untrusted: 清理释义html，不加载词典的js
+pub const DICT_TRUST: &[(&str, TrustLevel)] = &[("汉语词典3", TrustLevel::BundledScripts)];
+
+/// 释义html清理时，在默认白名单之外允许的标签
+pub const SANITIZE_EXTRA_TAGS: &[&str] = &["audio", "big", "center", "font", "source"];
+
+/// 所有标签都允许的属性
+pub const SANITIZE_GENERIC_ATTRIBUTES: &[&str] = &["class", "id", "style"];
+
+/// 只在某个标签上允许的属性
+pub const SANITIZE_TAG_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("audio", &["src", "controls", "preload"]),
+    ("source", &["src", "type"]),
+    ("font", &["color", "face", "size"]),
+];
+
+/// 词典名称，即词典文件名去掉扩展名，如 "牛津高阶8"，"foo.dsl.dz" 为 "foo"
+pub fn dict_name(file: &str) -> String {
+    let file = file.strip_suffix(".dsl.dz").unwrap_or(file);
+    Path::new(file)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| file.to_string())
+}
+
+/// mdx同目录下同名的mdd资源文件，如 牛津高阶8.mdd 牛津高阶8.1.mdd 牛津高阶8.2.mdd
+pub fn mdd_files(file: &str) -> Vec<PathBuf> {
+    let path = Path::new(file);
+    let stem = dict_name(file);
+    let mut files = vec![path.with_file_name(format!("{stem}.mdd"))];
+    files.extend(
+        (1..)
+            .map(|i| path.with_file_name(format!("{stem}.{i}.mdd")))
+            .take_while(|p| p.exists()),
+    );
+    files.retain(|p| p.exists());
+    files
+}
+
 /// 获取项目root dir
 pub fn static_path() -> anyhow::Result<PathBuf> {
     let mut path: PathBuf = env!("CARGO_MANIFEST_DIR").into();
@@ -24,41 +83,63 @@ pub fn static_path() -> anyhow::Result<PathBuf> {
 pub static DB_POOLS: LazyLock<HashMap<String, Pool<SqliteConnectionManager>>> =
     LazyLock::new(|| {
         info!("initializing pools...");
-        let mut pools = HashMap::new();
-
-        for file in MDX_FILES {
-            let db_file = format!("{file}.db");
-            let manager = SqliteConnectionManager::file(&db_file).with_init(|conn| {
-                // 设置SQLite性能优化参数
-                conn.pragma_update(None, "journal_mode", "WAL").unwrap();
-                conn.pragma_update(None, "synchronous", "NORMAL").unwrap();
-                conn.pragma_update(None, "cache_size", "-64000").unwrap(); // 64MB cache
-                conn.pragma_update(None, "busy_timeout", "5000").unwrap(); // 5 second busy timeout
-                Ok(())
-            });
-
-            // 创建连接池，设置最大连接数为10，最小连接数为2
-            let pool = Pool::builder()
-                .max_size(10)
-                .min_idle(Some(2))
-                .build(manager)
-                .unwrap_or_else(|_| panic!("Failed to create connection pool for {}", db_file));
-
-            pools.insert(file.to_string(), pool);
-        }
+        MDX_FILES
+            .iter()
+            .map(|file| {
+                let pool = create_pool(file)
+                    .unwrap_or_else(|_| panic!("Failed to create connection pool for {file}.db"));
+                (file.to_string(), pool)
+            })
+            .collect()
+    });
+
+/// 不在 MDX_FILES 中的词典，如命令行工具和测试中索引的文件，第一次使用时创建连接池
+static EXTRA_POOLS: LazyLock<Mutex<HashMap<String, Pool<SqliteConnectionManager>>>> =
+    LazyLock::new(Default::default);
 
-        pools
+/// 词典的 {file}.db 连接池
+fn create_pool(file: &str) -> Result<Pool<SqliteConnectionManager>, r2d2::Error> {
+    let db_file = format!("{file}.db");
+    let manager = SqliteConnectionManager::file(&db_file).with_init(|conn| {
+        // 设置SQLite性能优化参数
+        conn.pragma_update(None, "journal_mode", "WAL").unwrap();
+        conn.pragma_update(None, "synchronous", "NORMAL").unwrap();
+        conn.pragma_update(None, "cache_size", "-64000").unwrap(); // 64MB cache
+        conn.pragma_update(None, "busy_timeout", "5000").unwrap(); // 5 second busy timeout
+        Ok(())
     });
 
+    // 创建连接池，设置最大连接数为10，最小连接数为2
+    Pool::builder()
+        .max_size(10)
+        .min_idle(Some(2))
+        .build(manager)
+}
+
 /// 从连接池获取数据库连接
 pub fn get_db_connection(
     file: &str,
 ) -> anyhow::Result<r2d2::PooledConnection<SqliteConnectionManager>> {
     info!("get connection from pool...");
-    let pools = &*DB_POOLS;
-    let pool = pools
-        .get(file)
-        .ok_or_else(|| anyhow::anyhow!("No connection pool found for file: {}", file))?;
+    let pool = match DB_POOLS.get(file) {
+        Some(pool) => pool.clone(),
+        None => {
+            let mut pools = EXTRA_POOLS.lock().unwrap_or_else(|e| e.into_inner());
+            match pools.get(file) {
+                Some(pool) => pool.clone(),
+                None => {
+                    if !Path::new(&format!("{file}.db")).exists() {
+                        anyhow::bail!("No connection pool found for file: {}", file);
+                    }
+                    let pool = create_pool(file).map_err(|e| {
+                        anyhow::anyhow!("Failed to create connection pool for {}: {}", file, e)
+                    })?;
+                    pools.insert(file.to_string(), pool.clone());
+                    pool
+                }
+            }
+        }
+    };
 
     pool.get()
         .map_err(|e| anyhow::anyhow!("Failed to get connection from pool: {}", e))
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use ego_tree::NodeRef;
+use scraper::{Html, Node};
+
+use crate::convert::zip::ZipWriter;
+use crate::convert::{Entries, Metadata, entry_link, utc_timestamp};
+use crate::rewrite::local_path;
+use crate::util::{escape_html, normalize_resource_path};
+
+/// 每个xhtml文件中的词条数，Kindle对单个文件的大小有限制
+const ENTRIES_PER_FILE: usize = 1000;
+/// Kindle词典标记 idx:entry mbp:frameset 的命名空间
+const KINDLE_NS: &str = "https://kindlegen.s3.amazonaws.com/AmazonKindlePublishingGuidelines.pdf";
+
+/// 释义中保留的html标签，其他标签只保留内容
+const TAGS: &[&str] = &[
+    "a",
+    "abbr",
+    "b",
+    "big",
+    "blockquote",
+    "br",
+    "cite",
+    "code",
+    "dd",
+    "del",
+    "dfn",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "ins",
+    "kbd",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "q",
+    "rp",
+    "rt",
+    "ruby",
+    "s",
+    "samp",
+    "small",
+    "span",
+    "strong",
+    "sub",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "u",
+    "ul",
+    "var",
+];
+/// 连同内容一起去掉的标签
+const SKIPPED_TAGS: &[&str] = &[
+    "script", "style", "head", "link", "meta", "title", "template", "audio", "video", "object",
+    "embed", "iframe", "input", "button", "select", "textarea",
+];
+/// 保留的属性，a的href和img的src单独处理
+const ATTRIBUTES: &[&str] = &[
+    "class", "style", "title", "alt", "colspan", "rowspan", "lang", "dir",
+];
+
+/// 写入EPUB3词典: 每个词条是Kindle的 idx:entry，别名作为 idx:iform 变形词
+/// 释义转为xhtml，词条链接指向书中的词条，释义引用的mdd图片放入 res/ 目录
+pub fn write_epub(
+    entries: &Entries,
+    metadata: &Metadata,
+    resources: &HashMap<String, Vec<u8>>,
+    out: impl Write,
+) -> anyhow::Result<()> {
+    let mut book = Book {
+        anchors: HashMap::new(),
+        resources,
+        images: vec![],
+        image_names: HashMap::new(),
+    };
+    for (i, (word, _)) in entries.items.iter().enumerate() {
+        book.anchors.insert(word, i);
+    }
+    for (alias, word) in &entries.aliases {
+        if let Some(&i) = book.anchors.get(word.as_str()) {
+            book.anchors.entry(alias).or_insert(i);
+        }
+    }
+
+    let aliases = entries.aliases_by_headword();
+    let mut files = vec![];
+    for (n, chunk) in entries.items.chunks(ENTRIES_PER_FILE).enumerate() {
+        let mut body = String::new();
+        for (i, (word, html)) in chunk.iter().enumerate() {
+            let id = n * ENTRIES_PER_FILE + i;
+            let label = xml_text(word);
+            body.push_str(&format!(
+                r#"<idx:entry name="default" scriptable="yes" spell="yes"><idx:orth value="{label}"><b class="hw" id="e{id}">{label}</b>"#
+            ));
+            if let Some(forms) = aliases.get(word.as_str()) {
+                body.push_str("<idx:infl>");
+                for form in forms {
+                    body.push_str(&format!(r#"<idx:iform value="{}"/>"#, xml_text(form)));
+                }
+                body.push_str("</idx:infl>");
+            }
+            body.push_str(r#"</idx:orth><div class="def">"#);
+            let fragment = Html::parse_fragment(html);
+            book.children(*fragment.root_element(), &mut body);
+            body.push_str("</div></idx:entry><hr/>\n");
+        }
+        let first = &chunk[0].0;
+        let last = &chunk[chunk.len() - 1].0;
+        files.push((format!("entries-{}.xhtml", n + 1), first, last, body));
+    }
+
+    let title = xml_text(&metadata.title);
+    let language = xml_text(&metadata.source_language);
+    let mut zip = ZipWriter::new(out);
+    // mimetype必须是第一个文件并且不压缩
+    zip.add("mimetype", b"application/epub+zip", false)?;
+    zip.add("META-INF/container.xml", CONTAINER.as_bytes(), true)?;
+    zip.add("OEBPS/style.css", STYLE.as_bytes(), true)?;
+
+    let mut toc = String::new();
+    for (name, first, last, body) in &files {
+        let page = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:idx="{KINDLE_NS}" xmlns:mbp="{KINDLE_NS}" lang="{language}" xml:lang="{language}">
+<head><meta charset="UTF-8"/><title>{title}</title><link rel="stylesheet" type="text/css" href="style.css"/></head>
+<body><mbp:frameset>
+{body}</mbp:frameset></body>
+</html>
+"#
+        );
+        zip.add(&format!("OEBPS/{name}"), page.as_bytes(), true)?;
+        toc.push_str(&format!(
+            r#"<li><a href="{name}">{} – {}</a></li>"#,
+            xml_text(first),
+            xml_text(last)
+        ));
+    }
+    let nav = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" lang="{language}" xml:lang="{language}">
+<head><meta charset="UTF-8"/><title>{title}</title></head>
+<body><nav epub:type="toc"><h1>{title}</h1><ol>{toc}</ol></nav></body>
+</html>
+"#
+    );
+    zip.add("OEBPS/nav.xhtml", nav.as_bytes(), true)?;
+
+    let mut manifest = String::from(
+        r#"<item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+<item id="css" href="style.css" media-type="text/css"/>
+"#,
+    );
+    let mut spine = String::new();
+    for (i, (name, ..)) in files.iter().enumerate() {
+        manifest.push_str(&format!(
+            r#"<item id="entries-{}" href="{name}" media-type="application/xhtml+xml"/>
+"#,
+            i + 1
+        ));
+        spine.push_str(&format!(r#"<itemref idref="entries-{}"/>"#, i + 1));
+    }
+    for (i, (name, path)) in book.images.iter().enumerate() {
+        let mime = mime_guess::from_path(name).first_or_octet_stream();
+        manifest.push_str(&format!(
+            r#"<item id="r{}" href="{name}" media-type="{mime}"/>
+"#,
+            i + 1
+        ));
+        zip.add(&format!("OEBPS/{name}"), &resources[path.as_str()], false)?;
+    }
+
+    let description = match metadata.description.as_str() {
+        "" => String::new(),
+        d => format!("<dc:description>{}</dc:description>\n", xml_text(d)),
+    };
+    let opf = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+<metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+<dc:identifier id="uid">urn:mdict-rs:{title}</dc:identifier>
+<dc:title>{title}</dc:title>
+<dc:language>{language}</dc:language>
+{description}<meta property="dcterms:modified">{}</meta>
+<meta name="DictionaryInLanguage" content="{language}"/>
+<meta name="DictionaryOutLanguage" content="{}"/>
+<meta name="DefaultLookupIndex" content="default"/>
+</metadata>
+<manifest>
+{manifest}</manifest>
+<spine>{spine}</spine>
+</package>
+"#,
+        utc_timestamp(),
+        xml_text(&metadata.target_language)
+    );
+    zip.add("OEBPS/content.opf", opf.as_bytes(), true)?;
+    zip.finish()?;
+    Ok(())
+}
+
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+<rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>
+"#;
+
+const STYLE: &str =
+    "b.hw { font-size: 1.2em; }\ndiv.def { margin: 0.3em 0 0.6em; }\nimg { max-width: 100%; }\n";
+
+/// 释义html转xhtml时需要的词条位置和资源
+struct Book<'a> {
+    // 词头和别名 -> 词条序号，词条链接改为书中的锚点
+    anchors: HashMap<&'a str, usize>,
+    resources: &'a HashMap<String, Vec<u8>>,
+    // 用到的图片: (epub中的文件名, mdd中的路径)
+    images: Vec<(String, String)>,
+    image_names: HashMap<String, String>,
+}
+
+impl Book<'_> {
+    fn children(&mut self, node: NodeRef<Node>, out: &mut String) {
+        for child in node.children() {
+            self.node(child, out);
+        }
+    }
+
+    /// 只输出白名单中的标签和属性，保证是合法的xhtml
+    fn node(&mut self, node: NodeRef<Node>, out: &mut String) {
+        let el = match node.value() {
+            Node::Text(text) => return out.push_str(&xml_text(text)),
+            Node::Element(el) => el,
+            _ => return,
+        };
+        let name = el.name();
+        if SKIPPED_TAGS.contains(&name) {
+            return;
+        }
+        let (tag, mut attributes) = match name {
+            "font" => {
+                let style = el.attr("color").map(|c| format!("color:{c}"));
+                (
+                    "span",
+                    style.map(|s| vec![("style", s)]).unwrap_or_default(),
+                )
+            }
+            "center" => ("div", vec![("style", "text-align:center".to_string())]),
+            "a" => match el.attr("href").and_then(|href| self.href(href)) {
+                Some(href) => ("a", vec![("href", href)]),
+                None => return self.children(node, out),
+            },
+            "img" => match el.attr("src").and_then(|src| self.image(src)) {
+                Some(src) => ("img", vec![("src", src)]),
+                None => return,
+            },
+            name if TAGS.contains(&name) => (name, vec![]),
+            _ => return self.children(node, out),
+        };
+        for attribute in ATTRIBUTES {
+            if let Some(value) = el.attr(attribute)
+                && !attributes.iter().any(|(a, _)| a == attribute)
+            {
+                attributes.push((*attribute, value.to_string()));
+            }
+        }
+        if tag == "img" && !attributes.iter().any(|(a, _)| *a == "alt") {
+            attributes.push(("alt", String::new()));
+        }
+
+        out.push('<');
+        out.push_str(tag);
+        for (attribute, value) in &attributes {
+            out.push_str(&format!(r#" {attribute}="{}""#, xml_text(value)));
+        }
+        if matches!(tag, "br" | "hr" | "img") {
+            out.push_str("/>");
+            return;
+        }
+        out.push('>');
+        self.children(node, out);
+        out.push_str(&format!("</{tag}>"));
+    }
+
+    /// 词条链接改为书中词条的锚点，网页链接保留，发音和其他链接去掉
+    fn href(&self, href: &str) -> Option<String> {
+        if let Some(word) = entry_link(href) {
+            let id = self.anchors.get(word)?;
+            return Some(format!("entries-{}.xhtml#e{id}", id / ENTRIES_PER_FILE + 1));
+        }
+        (href.starts_with("http://") || href.starts_with("https://")).then(|| href.to_string())
+    }
+
+    /// mdd中的图片复制到 res/，文件名按出现顺序编号
+    fn image(&mut self, src: &str) -> Option<String> {
+        if src.starts_with("http://") || src.starts_with("https://") {
+            return Some(src.to_string());
+        }
+        let path = normalize_resource_path(local_path(src)?);
+        if let Some(name) = self.image_names.get(&path) {
+            return Some(name.clone());
+        }
+        let mime = mime_guess::from_path(&path).first()?;
+        if mime.type_() != "image" || !self.resources.contains_key(&path) {
+            return None;
+        }
+        let extension = path.rsplit_once('.').map_or("", |(_, e)| e);
+        let name = format!("res/r{}.{extension}", self.images.len() + 1);
+        self.images.push((name.clone(), path.clone()));
+        self.image_names.insert(path, name.clone());
+        Some(name)
+    }
+}
+
+/// xml中的文本和属性值: 转义特殊字符，去掉xml不允许的控制字符
+fn xml_text(text: &str) -> String {
+    let text: String = text
+        .chars()
+        .filter(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+    escape_html(&text)
+}
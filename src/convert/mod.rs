@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::mdict::MdxReader;
+use crate::mdict::mdd::Mdd;
+use crate::query::{MAX_REDIRECTS, link_target};
+use crate::util::normalize_resource_path;
+
+pub mod epub;
+pub mod stardict;
+pub mod yomitan;
+mod zip;
+
+/// mdx转换的目标格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConvertFormat {
+    // .ifo .idx .dict.dz .syn 以及 res/ 资源目录
+    StarDict,
+    // Yomitan(Yomichan)词典zip，index.json 和 term_bank_N.json
+    Yomitan,
+    // EPUB3词典，带Kindle的 idx:entry 和变形词索引
+    Epub,
+}
+
+impl ConvertFormat {
+    /// "stardict" -> StarDict, "yomitan" "yomichan" -> Yomitan, "epub" "kindle" -> Epub
+    pub fn parse(name: &str) -> Option<ConvertFormat> {
+        match name.trim().to_lowercase().as_str() {
+            "stardict" => Some(ConvertFormat::StarDict),
+            "yomitan" | "yomichan" => Some(ConvertFormat::Yomitan),
+            "epub" | "kindle" => Some(ConvertFormat::Epub),
+            _ => None,
+        }
+    }
+}
+
+/// 转换后词典的信息，语言是 "en" "zh" 这样的代码
+pub struct Metadata {
+    pub title: String,
+    pub description: String,
+    // 词头的语言
+    pub source_language: String,
+    // 释义的语言
+    pub target_language: String,
+}
+
+/// 转换用的词条: 按mdx顺序的(词头, 释义html)，同名词条合并
+/// 以及由 @@@LINK= 跳转词条得到的(别名, 词头)，如 ("apples", "apple")
+pub struct Entries {
+    pub items: Vec<(String, String)>,
+    pub aliases: Vec<(String, String)>,
+}
+
+impl Entries {
+    /// 流式读取mdx，每个block只解压一次
+    pub fn from_mdx(mdx: &mut MdxReader<impl Read + Seek>) -> anyhow::Result<Entries> {
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut items: Vec<(String, String)> = vec![];
+        let mut links: HashMap<String, String> = HashMap::new();
+        for r in mdx.records() {
+            let r = r?;
+            let definition = r.definition();
+            let def = definition.trim_end_matches('\0');
+            if let Some(target) = link_target(def) {
+                links.insert(r.text, target.to_string());
+                continue;
+            }
+            match index.get(&r.text) {
+                Some(&i) => {
+                    items[i].1.push_str("<hr/>");
+                    items[i].1.push_str(def);
+                }
+                None => {
+                    index.insert(r.text.clone(), items.len());
+                    items.push((r.text, def.to_string()));
+                }
+            }
+        }
+
+        // 跟随跳转直到有释义的词头，目标不存在的跳转丢弃
+        let mut aliases: Vec<(String, String)> = links
+            .keys()
+            .filter_map(|alias| {
+                let mut target = links.get(alias)?;
+                for _ in 0..MAX_REDIRECTS {
+                    if index.contains_key(target) {
+                        return (alias != target).then(|| (alias.clone(), target.clone()));
+                    }
+                    target = links.get(target)?;
+                }
+                None
+            })
+            .collect();
+        aliases.sort();
+        Ok(Entries { items, aliases })
+    }
+
+    /// 词头 -> 别名列表
+    pub fn aliases_by_headword(&self) -> HashMap<&str, Vec<&str>> {
+        let mut map: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (alias, headword) in &self.aliases {
+            map.entry(headword).or_default().push(alias);
+        }
+        map
+    }
+}
+
+/// entry://apple 和 bword://apple 中的词头，页内锚点 entry://#sec 除外
+pub(crate) fn entry_link(href: &str) -> Option<&str> {
+    let target = href
+        .strip_prefix("entry://")
+        .or_else(|| href.strip_prefix("bword://"))?;
+    let word = target.split('#').next().unwrap_or_default().trim();
+    (!word.is_empty()).then_some(word)
+}
+
+/// mdd中的资源文件，key是 normalize_resource_path 后的路径
+pub fn mdd_resources(mdds: &[Mdd]) -> anyhow::Result<HashMap<String, Vec<u8>>> {
+    let mut resources = HashMap::new();
+    for r in mdds.iter().flat_map(Mdd::items) {
+        let r = r?;
+        resources.insert(normalize_resource_path(r.path), r.data);
+    }
+    Ok(resources)
+}
+
+/// 当前的UTC时间，如 "2024-05-01T08:00:00Z"
+pub(crate) fn utc_timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // 1970-01-01起的天数转为年月日，见 http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
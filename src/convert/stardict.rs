@@ -0,0 +1,122 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use anyhow::Context;
+use lol_html::{RewriteStrSettings, element, rewrite_str};
+
+use crate::convert::{Entries, Metadata, entry_link};
+use crate::stardict::{IFO_MAGIC, write_dictzip};
+
+/// 写入StarDict词典，ifo是 .ifo 文件的路径，同目录下写入同名的 .idx .dict.dz，有别名时还有 .syn
+/// 释义为html(sametypesequence=h)，词条链接改为StarDict使用的 bword://
+pub fn write_stardict(entries: &Entries, metadata: &Metadata, ifo: &Path) -> anyhow::Result<()> {
+    let mut items: Vec<&(String, String)> = entries.items.iter().collect();
+    items.sort_by(|a, b| stardict_cmp(&a.0, &b.0));
+
+    let mut dict = vec![];
+    let mut ranges = vec![];
+    for (_, html) in &items {
+        let html = bword_links(html)?;
+        ranges.push((dict.len(), html.len()));
+        dict.extend_from_slice(html.as_bytes());
+    }
+    let offset64 = dict.len() > u32::MAX as usize;
+
+    let mut idx = vec![];
+    for ((word, _), (offset, size)) in items.iter().zip(ranges) {
+        idx.extend_from_slice(word.as_bytes());
+        idx.push(0);
+        if offset64 {
+            idx.extend_from_slice(&(offset as u64).to_be_bytes());
+        } else {
+            idx.extend_from_slice(&(offset as u32).to_be_bytes());
+        }
+        idx.extend_from_slice(&(size as u32).to_be_bytes());
+    }
+
+    // .syn 中是别名和词头在 .idx 中的序号
+    let positions: HashMap<&str, usize> = items
+        .iter()
+        .enumerate()
+        .map(|(i, (word, _))| (word.as_str(), i))
+        .collect();
+    let mut synonyms: Vec<(&str, usize)> = entries
+        .aliases
+        .iter()
+        .filter_map(|(alias, word)| Some((alias.as_str(), *positions.get(word.as_str())?)))
+        .collect();
+    synonyms.sort_by(|a, b| stardict_cmp(a.0, b.0));
+    let mut syn = vec![];
+    for (alias, i) in &synonyms {
+        syn.extend_from_slice(alias.as_bytes());
+        syn.push(0);
+        syn.extend_from_slice(&(*i as u32).to_be_bytes());
+    }
+
+    let write = |path: &Path, data: &[u8]| {
+        fs::write(path, data).with_context(|| format!("write {} failed", path.display()))
+    };
+    write(&ifo.with_extension("idx"), &idx)?;
+    let dz = ifo.with_extension("dict.dz");
+    let mut out = BufWriter::new(
+        fs::File::create(&dz).with_context(|| format!("create {} failed", dz.display()))?,
+    );
+    write_dictzip(&dict, &mut out)?;
+    out.flush()?;
+    if !synonyms.is_empty() {
+        write(&ifo.with_extension("syn"), &syn)?;
+    }
+
+    let mut info = format!(
+        "{IFO_MAGIC}\nversion=3.0.0\nbookname={}\nwordcount={}\nidxfilesize={}\nsametypesequence=h\n",
+        one_line(&metadata.title),
+        items.len(),
+        idx.len()
+    );
+    if !synonyms.is_empty() {
+        info.push_str(&format!("synwordcount={}\n", synonyms.len()));
+    }
+    if offset64 {
+        info.push_str("idxoffsetbits=64\n");
+    }
+    if !metadata.description.is_empty() {
+        info.push_str(&format!(
+            "description={}\n",
+            one_line(&metadata.description)
+        ));
+    }
+    write(ifo, info.as_bytes())
+}
+
+/// StarDict的词头顺序: 先按ascii忽略大小写比较，相同时再区分大小写
+fn stardict_cmp(a: &str, b: &str) -> Ordering {
+    let lower = |s: &str| {
+        s.bytes()
+            .map(|b| b.to_ascii_lowercase())
+            .collect::<Vec<_>>()
+    };
+    lower(a).cmp(&lower(b)).then_with(|| a.cmp(b))
+}
+
+/// ifo中的值只能占一行，换行改为<br>
+fn one_line(text: &str) -> String {
+    text.replace("\r\n", "<br>").replace(['\r', '\n'], "<br>")
+}
+
+/// entry://apple -> bword://apple
+fn bword_links(html: &str) -> anyhow::Result<String> {
+    let rewritten = rewrite_str(
+        html,
+        RewriteStrSettings::new().append_element_content_handler(element!("a[href]", |el| {
+            let href = el.get_attribute("href").unwrap_or_default();
+            if let Some(word) = entry_link(&href) {
+                el.set_attribute("href", &format!("bword://{word}"))?;
+            }
+            Ok(())
+        })),
+    )?;
+    Ok(rewritten)
+}
@@ -0,0 +1,58 @@
+use std::io::Write;
+
+use serde_json::{Value, json};
+
+use crate::convert::zip::ZipWriter;
+use crate::convert::{Entries, Metadata, utc_timestamp};
+use crate::query::{TextFormat, html_to_text};
+
+/// 每个 term_bank_N.json 中的词条数
+const TERMS_PER_BANK: usize = 10000;
+
+/// 写入Yomitan词典zip，释义转为纯文本
+/// 别名写为指向词头的变形词条，Yomitan查询别名时显示词头的释义
+pub fn write_yomitan(
+    entries: &Entries,
+    metadata: &Metadata,
+    out: impl Write,
+) -> anyhow::Result<()> {
+    // [词, 读音, 释义标签, 变形规则, 分数, [释义], 序号, 词条标签]
+    let mut terms: Vec<Value> = vec![];
+    for (word, html) in &entries.items {
+        let text = html_to_text(html, TextFormat::Text);
+        if text.is_empty() {
+            continue;
+        }
+        terms.push(json!([word, "", "", "", 0, [text], terms.len() + 1, ""]));
+    }
+    for (alias, word) in &entries.aliases {
+        terms.push(json!([
+            alias,
+            "",
+            "",
+            "",
+            0,
+            [[word, []]],
+            terms.len() + 1,
+            ""
+        ]));
+    }
+
+    let mut zip = ZipWriter::new(out);
+    let index = json!({
+        "title": metadata.title,
+        "revision": utc_timestamp(),
+        "format": 3,
+        "sequenced": true,
+        "description": metadata.description,
+        "sourceLanguage": metadata.source_language,
+        "targetLanguage": metadata.target_language,
+    });
+    zip.add("index.json", &serde_json::to_vec(&index)?, true)?;
+    for (i, bank) in terms.chunks(TERMS_PER_BANK).enumerate() {
+        let name = format!("term_bank_{}.json", i + 1);
+        zip.add(&name, &serde_json::to_vec(bank)?, true)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
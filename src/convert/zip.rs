@@ -0,0 +1,122 @@
+use std::io::Write;
+
+use anyhow::bail;
+use flate2::Compression;
+use flate2::Crc;
+use flate2::write::DeflateEncoder;
+
+/// 文件的修改时间固定为 1980-01-01 00:00，zip中的dos日期格式
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// 中央目录中的一项
+struct ZipEntry {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// 简单的zip写入，不支持zip64，文件和总大小都不能超过4GB
+pub(crate) struct ZipWriter<W: Write> {
+    out: W,
+    offset: u64,
+    entries: Vec<ZipEntry>,
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub(crate) fn new(out: W) -> ZipWriter<W> {
+        ZipWriter {
+            out,
+            offset: 0,
+            entries: vec![],
+        }
+    }
+
+    /// 添加一个文件，deflate为false时不压缩(stored)，如epub的mimetype
+    pub(crate) fn add(&mut self, name: &str, data: &[u8], deflate: bool) -> anyhow::Result<()> {
+        let mut crc = Crc::new();
+        crc.update(data);
+        let (method, compressed) = if deflate {
+            let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+            encoder.write_all(data)?;
+            (8, encoder.finish()?)
+        } else {
+            (0, data.to_vec())
+        };
+        if self.offset > u32::MAX as u64 || data.len() > u32::MAX as usize {
+            bail!("zip file is larger than 4GB");
+        }
+        let entry = ZipEntry {
+            name: name.to_string(),
+            method,
+            crc: crc.sum(),
+            compressed_size: compressed.len() as u32,
+            size: data.len() as u32,
+            offset: self.offset as u32,
+        };
+
+        let mut header = vec![];
+        header.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        header.extend_from_slice(&20u16.to_le_bytes());
+        // bit 11: 文件名是UTF-8
+        header.extend_from_slice(&(1u16 << 11).to_le_bytes());
+        header.extend_from_slice(&entry.method.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(&DOS_DATE.to_le_bytes());
+        header.extend_from_slice(&entry.crc.to_le_bytes());
+        header.extend_from_slice(&entry.compressed_size.to_le_bytes());
+        header.extend_from_slice(&entry.size.to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+        self.out.write_all(&header)?;
+        self.out.write_all(&compressed)?;
+        self.offset += (header.len() + compressed.len()) as u64;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// 写入中央目录和结尾记录
+    pub(crate) fn finish(mut self) -> anyhow::Result<W> {
+        let start = self.offset;
+        let mut directory = vec![];
+        for entry in &self.entries {
+            directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+            // made by: 3(unix) 2.0
+            directory.extend_from_slice(&0x0314u16.to_le_bytes());
+            directory.extend_from_slice(&20u16.to_le_bytes());
+            directory.extend_from_slice(&(1u16 << 11).to_le_bytes());
+            directory.extend_from_slice(&entry.method.to_le_bytes());
+            directory.extend_from_slice(&0u16.to_le_bytes());
+            directory.extend_from_slice(&DOS_DATE.to_le_bytes());
+            directory.extend_from_slice(&entry.crc.to_le_bytes());
+            directory.extend_from_slice(&entry.compressed_size.to_le_bytes());
+            directory.extend_from_slice(&entry.size.to_le_bytes());
+            directory.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            // extra, comment, disk, internal attributes
+            directory.extend_from_slice(&[0; 8]);
+            // external attributes: 普通文件 0644
+            directory.extend_from_slice(&(0o100644u32 << 16).to_le_bytes());
+            directory.extend_from_slice(&entry.offset.to_le_bytes());
+            directory.extend_from_slice(entry.name.as_bytes());
+        }
+        if start + directory.len() as u64 > u32::MAX as u64 || self.entries.len() > 0xffff {
+            bail!("zip file is larger than 4GB or has too many files");
+        }
+
+        let mut end = vec![];
+        end.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        end.extend_from_slice(&[0; 4]);
+        end.extend_from_slice(&(self.entries.len() as u16).to_le_bytes());
+        end.extend_from_slice(&(self.entries.len() as u16).to_le_bytes());
+        end.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        end.extend_from_slice(&(start as u32).to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        self.out.write_all(&directory)?;
+        self.out.write_all(&end)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
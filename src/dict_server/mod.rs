@@ -0,0 +1,498 @@
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{info, warn};
+
+use crate::dictionary::{Dictionary, find_dictionary};
+use crate::query::{LookupResult, TextFormat, html_to_text, lookup_all};
+
+mod strategy;
+
+pub use strategy::{STRATEGIES, Strategy, find_matches};
+
+/// 命令行的最大长度，RFC 2229 规定为1024
+const MAX_LINE: usize = 1024;
+/// 连接的序号，用于欢迎信息中的msg-id
+static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// DICT协议(RFC 2229)服务，每个词典是一个database，名称与web服务中的词典名称相同
+pub async fn serve(dicts: Vec<Arc<dyn Dictionary>>, addr: &str) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("dict server on {}", addr);
+    let dicts: Arc<[Arc<dyn Dictionary>]> = dicts.into();
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let dicts = dicts.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, dicts).await {
+                warn!("dict connection {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+/// 一个客户端连接，逐行读取命令，查询在阻塞线程中执行
+async fn handle_connection(
+    stream: TcpStream,
+    dicts: Arc<[Arc<dyn Dictionary>]>,
+) -> anyhow::Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut reader = BufReader::new(read);
+    let id = CONNECTIONS.fetch_add(1, Ordering::Relaxed);
+    let banner = format!(
+        "220 mdict-rs <mime> <{}.{}@mdict-rs>\r\n",
+        process::id(),
+        id
+    );
+    write.write_all(banner.as_bytes()).await?;
+
+    let mut mime = false;
+    loop {
+        let mut line = vec![];
+        let n = (&mut reader)
+            .take(MAX_LINE as u64 + 1)
+            .read_until(b'\n', &mut line)
+            .await?;
+        if n == 0 {
+            return Ok(());
+        }
+        if line.len() > MAX_LINE {
+            write.write_all(b"500 line too long\r\n").await?;
+            return Ok(());
+        }
+
+        let line = String::from_utf8_lossy(&line).trim().to_string();
+        if line.is_empty() {
+            continue;
+        }
+        let response = match parse_command(&line) {
+            Ok(Command::Quit) => {
+                write.write_all(b"221 bye\r\n").await?;
+                return Ok(());
+            }
+            Ok(Command::OptionMime) => {
+                mime = true;
+                "250 ok - using MIME headers\r\n".to_string()
+            }
+            Ok(command) => {
+                let dicts = dicts.clone();
+                tokio::task::spawn_blocking(move || respond(&dicts, &command, mime)).await?
+            }
+            Err(status) => format!("{status}\r\n"),
+        };
+        write.write_all(response.as_bytes()).await?;
+    }
+}
+
+/// 客户端的命令，database为 "*" 时查询所有词典，"!" 时只返回第一个有结果的词典
+#[derive(Debug)]
+enum Command {
+    Define {
+        database: String,
+        word: String,
+    },
+    Match {
+        database: String,
+        strategy: String,
+        word: String,
+    },
+    ShowDatabases,
+    ShowStrategies,
+    ShowInfo(String),
+    ShowServer,
+    Client,
+    Status,
+    Help,
+    OptionMime,
+    Quit,
+    // AUTH SASLAUTH 等不支持的命令
+    NotImplemented,
+}
+
+/// 解析一行命令，命令名不区分大小写，失败时返回状态行
+fn parse_command(line: &str) -> Result<Command, &'static str> {
+    const SYNTAX_ERROR: &str = "501 syntax error, illegal parameters";
+    let words = split_words(line).ok_or(SYNTAX_ERROR)?;
+    let upper: Vec<String> = words.iter().take(2).map(|w| w.to_uppercase()).collect();
+    let args: Vec<&str> = words.iter().map(String::as_str).collect();
+    let command = match (
+        upper[0].as_str(),
+        upper.get(1).map(String::as_str),
+        &args[1..],
+    ) {
+        ("DEFINE", _, [database, word]) => Command::Define {
+            database: database.to_string(),
+            word: word.to_string(),
+        },
+        ("MATCH", _, [database, strategy, word]) => Command::Match {
+            database: database.to_string(),
+            strategy: strategy.to_string(),
+            word: word.to_string(),
+        },
+        ("SHOW", Some("DB" | "DATABASES"), [_]) => Command::ShowDatabases,
+        ("SHOW", Some("STRAT" | "STRATEGIES"), [_]) => Command::ShowStrategies,
+        ("SHOW", Some("INFO"), [_, database]) => Command::ShowInfo(database.to_string()),
+        ("SHOW", Some("SERVER"), [_]) => Command::ShowServer,
+        ("OPTION", Some("MIME"), [_]) => Command::OptionMime,
+        ("CLIENT", _, [_, ..]) => Command::Client,
+        ("STATUS", _, []) => Command::Status,
+        ("HELP", _, []) => Command::Help,
+        ("QUIT", _, []) => Command::Quit,
+        ("AUTH" | "SASLAUTH" | "SASLRESP", _, _) => Command::NotImplemented,
+        ("DEFINE" | "MATCH" | "SHOW" | "OPTION" | "CLIENT" | "STATUS" | "HELP" | "QUIT", _, _) => {
+            return Err(SYNTAX_ERROR);
+        }
+        _ => return Err("500 unknown command"),
+    };
+    Ok(command)
+}
+
+/// 按空白分隔参数，参数可以用单引号或双引号括起来，引号中的 \ 转义下一个字符
+fn split_words(line: &str) -> Option<Vec<String>> {
+    let mut words = vec![];
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(&first) = chars.peek() else {
+            break;
+        };
+        let mut word = String::new();
+        if first == '"' || first == '\'' {
+            chars.next();
+            loop {
+                match chars.next()? {
+                    '\\' => word.push(chars.next()?),
+                    c if c == first => break,
+                    c => word.push(c),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                word.push(c);
+            }
+        }
+        words.push(word);
+    }
+    (!words.is_empty()).then_some(words)
+}
+
+/// 执行查询类的命令，返回完整的响应
+fn respond(dicts: &[Arc<dyn Dictionary>], command: &Command, mime: bool) -> String {
+    let mut out = String::new();
+    match command {
+        Command::Define { database, word } => define(dicts, database, word, mime, &mut out),
+        Command::Match {
+            database,
+            strategy,
+            word,
+        } => match_words(dicts, database, strategy, word, &mut out),
+        Command::ShowDatabases => {
+            if dicts.is_empty() {
+                status(&mut out, "554 no databases present");
+            } else {
+                status(&mut out, &format!("110 {} databases present", dicts.len()));
+                let lines: Vec<String> = dicts
+                    .iter()
+                    .map(|d| {
+                        format!(
+                            "{} {}",
+                            atom(&d.info().name),
+                            quote(&description(d.as_ref()))
+                        )
+                    })
+                    .collect();
+                text(&mut out, &lines.join("\n"));
+                status(&mut out, "250 ok");
+            }
+        }
+        Command::ShowStrategies => {
+            status(
+                &mut out,
+                &format!("111 {} strategies available", STRATEGIES.len()),
+            );
+            let lines: Vec<String> = STRATEGIES
+                .iter()
+                .map(|(_, name, desc)| format!("{name} {}", quote(desc)))
+                .collect();
+            text(&mut out, &lines.join("\n"));
+            status(&mut out, "250 ok");
+        }
+        Command::ShowInfo(database) => match find_dictionary(dicts, database) {
+            Some(d) => {
+                let info = d.info();
+                let entries = d
+                    .entry_count()
+                    .map_or_else(|e| format!("unknown ({e})"), |n| n.to_string());
+                status(&mut out, &format!("112 information for {}", info.name));
+                text(
+                    &mut out,
+                    &format!(
+                        "{}\n\nformat: {}\nfile: {}\nentries: {}",
+                        info.name, info.format, info.file, entries
+                    ),
+                );
+                status(&mut out, "250 ok");
+            }
+            None => invalid_database(&mut out),
+        },
+        Command::ShowServer => {
+            status(&mut out, "114 server information");
+            text(
+                &mut out,
+                &format!(
+                    "mdict-rs {}\n\n{} databases, see SHOW DB",
+                    env!("CARGO_PKG_VERSION"),
+                    dicts.len()
+                ),
+            );
+            status(&mut out, "250 ok");
+        }
+        Command::Help => {
+            status(&mut out, "113 help text follows");
+            text(&mut out, HELP);
+            status(&mut out, "250 ok");
+        }
+        Command::Client => status(&mut out, "250 ok"),
+        Command::Status => status(&mut out, &format!("210 status: {} databases", dicts.len())),
+        Command::NotImplemented => status(&mut out, "502 command not implemented"),
+        // 在连接中处理
+        Command::OptionMime | Command::Quit => status(&mut out, "250 ok"),
+    }
+    out
+}
+
+const HELP: &str = "DEFINE database word         -- look up word in database
+MATCH database strategy word -- match word in database using strategy
+SHOW DB                      -- list all accessible databases
+SHOW STRAT                   -- list available matching strategies
+SHOW INFO database           -- provide information about the database
+SHOW SERVER                  -- provide site-specific information
+OPTION MIME                  -- use MIME headers
+CLIENT info                  -- identify client to server
+STATUS                       -- display timing information
+HELP                         -- display this help information
+QUIT                         -- terminate connection
+
+database \"*\" searches all databases, \"!\" stops at the first database with a result";
+
+/// DEFINE: 通过查询层查词，释义转为纯文本
+fn define(dicts: &[Arc<dyn Dictionary>], database: &str, word: &str, mime: bool, out: &mut String) {
+    let Some(filter) = database_filter(dicts, database) else {
+        return invalid_database(out);
+    };
+    let mut results: Vec<LookupResult> = match lookup_all(dicts, word, filter) {
+        Ok(results) => results,
+        Err(e) => {
+            warn!("dict define {} failed: {}", word, e);
+            return status(out, "420 server temporarily unavailable");
+        }
+    };
+    if database == "!" {
+        results.truncate(1);
+    }
+    if results.is_empty() {
+        return status(out, "552 no match");
+    }
+
+    status(out, &format!("150 {} definitions retrieved", results.len()));
+    for result in &results {
+        let dict = find_dictionary(dicts, &result.dictionary);
+        let desc = dict.map(|d| description(d.as_ref())).unwrap_or_default();
+        status(
+            out,
+            &format!(
+                "151 {} {} {}",
+                quote(&result.headword),
+                atom(&result.dictionary),
+                quote(&desc)
+            ),
+        );
+        let mut body = String::new();
+        if mime {
+            body.push_str(
+                "Content-type: text/plain; charset=utf-8\nContent-transfer-encoding: 8bit\n\n",
+            );
+        }
+        body.push_str(&html_to_text(&result.html, TextFormat::Text));
+        text(out, &body);
+    }
+    status(out, "250 ok");
+}
+
+/// MATCH: 在每个词典中按策略匹配词头
+fn match_words(
+    dicts: &[Arc<dyn Dictionary>],
+    database: &str,
+    strategy: &str,
+    word: &str,
+    out: &mut String,
+) {
+    let Some(filter) = database_filter(dicts, database) else {
+        return invalid_database(out);
+    };
+    let Some(strategy) = Strategy::parse(strategy) else {
+        return status(
+            out,
+            "551 invalid strategy, use SHOW STRAT for a list of strategies",
+        );
+    };
+
+    let mut lines = vec![];
+    for d in dicts {
+        let name = &d.info().name;
+        if filter.is_some_and(|f| f != name) {
+            continue;
+        }
+        let found = match find_matches(d.as_ref(), strategy, word) {
+            Ok(found) => found,
+            Err(e) if e.is::<regex::Error>() => {
+                return status(out, "501 syntax error, illegal parameters");
+            }
+            Err(e) => {
+                warn!("dict match {} in {} failed: {}", word, name, e);
+                return status(out, "420 server temporarily unavailable");
+            }
+        };
+        lines.extend(found.iter().map(|w| format!("{} {}", atom(name), quote(w))));
+        if database == "!" && !lines.is_empty() {
+            break;
+        }
+    }
+    if lines.is_empty() {
+        return status(out, "552 no match");
+    }
+    status(out, &format!("152 {} matches found", lines.len()));
+    text(out, &lines.join("\n"));
+    status(out, "250 ok");
+}
+
+/// "*" 和 "!" 查询所有词典，其他是词典名称，不存在时为None
+fn database_filter<'a>(
+    dicts: &[Arc<dyn Dictionary>],
+    database: &'a str,
+) -> Option<Option<&'a str>> {
+    match database {
+        "*" | "!" => Some(None),
+        name => find_dictionary(dicts, name).map(|_| Some(name)),
+    }
+}
+
+fn invalid_database(out: &mut String) {
+    status(
+        out,
+        "550 invalid database, use SHOW DB for list of databases",
+    );
+}
+
+/// SHOW DB 和 151 行中的词典说明
+fn description(dict: &dyn Dictionary) -> String {
+    let info = dict.info();
+    format!("{} ({})", info.name, info.format)
+}
+
+fn status(out: &mut String, line: &str) {
+    out.push_str(line);
+    out.push_str("\r\n");
+}
+
+/// 多行文本，以单独的 "." 行结束，"." 开头的行再加一个 "."
+fn text(out: &mut String, body: &str) {
+    for line in body.lines() {
+        if line.starts_with('.') {
+            out.push('.');
+        }
+        out.push_str(line);
+        out.push_str("\r\n");
+    }
+    out.push_str(".\r\n");
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// 词典名称中有空白或引号时加引号
+fn atom(s: &str) -> String {
+    if s.is_empty() || s.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'') {
+        quote(s)
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_quoted_words() {
+        assert_eq!(
+            split_words(r#"DEFINE * "ice cream""#).unwrap(),
+            ["DEFINE", "*", "ice cream"]
+        );
+        assert_eq!(
+            split_words(r#"  match wn 'it\'s'  prefix "#).unwrap(),
+            ["match", "wn", "it's", "prefix"]
+        );
+        assert_eq!(split_words(r#"define * """#).unwrap(), ["define", "*", ""]);
+        assert_eq!(split_words(r#"define * "apple"#), None);
+        assert_eq!(split_words("   "), None);
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert!(matches!(
+            parse_command("define 牛津高阶8 apple"),
+            Ok(Command::Define { database, word }) if database == "牛津高阶8" && word == "apple"
+        ));
+        assert!(matches!(
+            parse_command(r#"MATCH * prefix "ice c""#),
+            Ok(Command::Match { database, strategy, word })
+                if database == "*" && strategy == "prefix" && word == "ice c"
+        ));
+        assert!(matches!(
+            parse_command("show db"),
+            Ok(Command::ShowDatabases)
+        ));
+        assert!(matches!(
+            parse_command("SHOW STRAT"),
+            Ok(Command::ShowStrategies)
+        ));
+        assert!(matches!(
+            parse_command("show info 牛津高阶8"),
+            Ok(Command::ShowInfo(database)) if database == "牛津高阶8"
+        ));
+        assert!(matches!(
+            parse_command("client test 1.0"),
+            Ok(Command::Client)
+        ));
+        assert!(matches!(parse_command("quit"), Ok(Command::Quit)));
+        assert!(matches!(
+            parse_command("auth user secret"),
+            Ok(Command::NotImplemented)
+        ));
+    }
+
+    #[test]
+    fn command_errors() {
+        assert_eq!(
+            parse_command("define apple").unwrap_err(),
+            "501 syntax error, illegal parameters"
+        );
+        assert_eq!(
+            parse_command("quit now").unwrap_err(),
+            "501 syntax error, illegal parameters"
+        );
+        assert_eq!(
+            parse_command("lookup apple").unwrap_err(),
+            "500 unknown command"
+        );
+        assert_eq!(
+            parse_command("").unwrap_err(),
+            "501 syntax error, illegal parameters"
+        );
+    }
+}
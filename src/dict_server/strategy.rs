@@ -0,0 +1,203 @@
+use regex::RegexBuilder;
+
+use crate::dictionary::Dictionary;
+
+/// MATCH 支持的匹配策略，"." 使用默认的 prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    Exact,
+    Prefix,
+    Substring,
+    Regex,
+    Soundex,
+    Lev,
+}
+
+/// SHOW STRAT 中的策略名称和说明
+pub const STRATEGIES: &[(Strategy, &str, &str)] = &[
+    (Strategy::Exact, "exact", "Match headwords exactly"),
+    (Strategy::Prefix, "prefix", "Match prefixes"),
+    (
+        Strategy::Substring,
+        "substring",
+        "Match substring occurring anywhere in a headword",
+    ),
+    (
+        Strategy::Regex,
+        "re",
+        "POSIX 1003.2 (modern) regular expressions",
+    ),
+    (
+        Strategy::Soundex,
+        "soundex",
+        "Match using SOUNDEX algorithm",
+    ),
+    (
+        Strategy::Lev,
+        "lev",
+        "Match headwords within Levenshtein distance one",
+    ),
+];
+
+/// 每个词典最多返回的匹配数
+const MAX_MATCHES: usize = 200;
+/// 正则编译后的大小上限，避免客户端的正则占用过多内存
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+impl Strategy {
+    /// 策略名称忽略大小写，"." 是服务器的默认策略
+    pub fn parse(name: &str) -> Option<Strategy> {
+        if name == "." {
+            return Some(Strategy::Prefix);
+        }
+        let name = name.to_lowercase();
+        let name = match name.as_str() {
+            "regexp" => "re",
+            "substr" => "substring",
+            other => other,
+        };
+        STRATEGIES
+            .iter()
+            .find(|(_, n, _)| *n == name)
+            .map(|(s, _, _)| *s)
+    }
+}
+
+/// 在一个词典中按策略匹配词头，按词典顺序，正则无效时返回错误
+pub fn find_matches(
+    dict: &dyn Dictionary,
+    strategy: Strategy,
+    word: &str,
+) -> anyhow::Result<Vec<String>> {
+    let lower = word.to_lowercase();
+    match strategy {
+        // 和dictd一样不区分大小写
+        Strategy::Exact => {
+            let mut entry = dict.lookup(word)?;
+            if entry.is_none() {
+                entry = dict.lookup(&lower)?;
+            }
+            if entry.is_none() {
+                entry = dict.lookup_key(&lower)?;
+            }
+            Ok(entry.map(|e| e.headword).into_iter().collect())
+        }
+        Strategy::Prefix => dict.prefix(word, MAX_MATCHES),
+        Strategy::Substring => dict.scan(
+            &mut |text| text.to_lowercase().contains(&lower),
+            MAX_MATCHES,
+        ),
+        Strategy::Regex => {
+            let re = RegexBuilder::new(word)
+                .case_insensitive(true)
+                .size_limit(REGEX_SIZE_LIMIT)
+                .build()?;
+            dict.scan(&mut |text| re.is_match(text), MAX_MATCHES)
+        }
+        Strategy::Soundex => {
+            let code = soundex(word);
+            dict.scan(
+                &mut |text| code.is_some() && soundex(text) == code,
+                MAX_MATCHES,
+            )
+        }
+        Strategy::Lev => {
+            let chars: Vec<char> = lower.chars().collect();
+            dict.scan(
+                &mut |text| within_one_edit(&chars, &text.to_lowercase()),
+                MAX_MATCHES,
+            )
+        }
+    }
+}
+
+/// 英文的soundex编码，如 "Robert" -> "R163"，没有字母时为None
+fn soundex(word: &str) -> Option<String> {
+    fn digit(c: char) -> Option<char> {
+        match c {
+            'b' | 'f' | 'p' | 'v' => Some('1'),
+            'c' | 'g' | 'j' | 'k' | 'q' | 's' | 'x' | 'z' => Some('2'),
+            'd' | 't' => Some('3'),
+            'l' => Some('4'),
+            'm' | 'n' => Some('5'),
+            'r' => Some('6'),
+            _ => None,
+        }
+    }
+
+    let mut letters = word
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_lowercase());
+    let first = letters.next()?;
+    let mut code = first.to_ascii_uppercase().to_string();
+    let mut last = digit(first);
+    for c in letters {
+        let d = digit(c);
+        if d.is_some() && d != last {
+            code.extend(d);
+            if code.len() == 4 {
+                break;
+            }
+        }
+        // h和w不分隔相同的编码，元音分隔
+        if c != 'h' && c != 'w' {
+            last = d;
+        }
+    }
+    while code.len() < 4 {
+        code.push('0');
+    }
+    Some(code)
+}
+
+/// text和word的编辑距离不超过1，相同的不算
+fn within_one_edit(word: &[char], text: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let (short, long) = if word.len() <= text.len() {
+        (word, &text[..])
+    } else {
+        (&text[..], word)
+    };
+    if long.len() - short.len() > 1 || short == long {
+        return false;
+    }
+    let prefix = short.iter().zip(long).take_while(|(a, b)| a == b).count();
+    if short.len() == long.len() {
+        // 替换一个字符
+        short[prefix + 1..] == long[prefix + 1..]
+    } else {
+        // 插入一个字符
+        short[prefix..] == long[prefix + 1..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn soundex_codes() {
+        assert_eq!(soundex("Robert").as_deref(), Some("R163"));
+        assert_eq!(soundex("Rupert").as_deref(), Some("R163"));
+        assert_eq!(soundex("Tymczak").as_deref(), Some("T522"));
+        assert_eq!(soundex("Pfister").as_deref(), Some("P236"));
+        // h和w不分隔相同的编码
+        assert_eq!(soundex("Ashcraft").as_deref(), Some("A261"));
+        assert_eq!(soundex("Lee").as_deref(), Some("L000"));
+        assert_eq!(soundex("123"), None);
+    }
+
+    #[test]
+    fn one_edit() {
+        let word: Vec<char> = "apple".chars().collect();
+        for text in ["apply", "aple", "apples", "bapple", "appe"] {
+            assert!(within_one_edit(&word, text), "{text}");
+        }
+        for text in ["apple", "aplpe", "ap", "applesauce", "orange"] {
+            assert!(!within_one_edit(&word, text), "{text}");
+        }
+        let word: Vec<char> = "字典".chars().collect();
+        assert!(within_one_edit(&word, "词典"));
+    }
+}
@@ -0,0 +1,267 @@
+use rusqlite::{Connection, OptionalExtension, named_params};
+use tracing::info;
+
+use crate::config::get_db_connection;
+use crate::dictionary::{Dictionary, DictionaryInfo, Entry};
+use crate::query::Neighbours;
+use crate::resource::dir_resource;
+use crate::search::{SearchHit, highlight};
+use crate::util::normalize_resource_path;
+
+/// indexing 建立的sqlite索引中的词典，mdx stardict dsl 都使用同样的表
+pub struct IndexedDictionary {
+    info: DictionaryInfo,
+}
+
+impl IndexedDictionary {
+    pub fn new(file: &str) -> IndexedDictionary {
+        IndexedDictionary {
+            info: DictionaryInfo::new(file),
+        }
+    }
+
+    fn entry(&self, sql: &str, word: &str) -> anyhow::Result<Option<Entry>> {
+        let conn = get_db_connection(&self.info.file)?;
+        let entry = conn
+            .query_row(sql, named_params! { ":word": word }, |row| {
+                Ok(Entry {
+                    headword: row.get(0)?,
+                    definition: row.get(1)?,
+                })
+            })
+            .optional()?;
+        Ok(entry)
+    }
+}
+
+impl Dictionary for IndexedDictionary {
+    fn info(&self) -> &DictionaryInfo {
+        &self.info
+    }
+
+    fn lookup(&self, word: &str) -> anyhow::Result<Option<Entry>> {
+        self.entry(
+            "select text, def from MDX_INDEX WHERE text= :word limit 1;",
+            word,
+        )
+    }
+
+    /// MDX_KEYS 中有简繁体写法和拼音，以及stardict的同义词和dsl的其他词头
+    fn lookup_key(&self, key: &str) -> anyhow::Result<Option<Entry>> {
+        self.entry(
+            "select i.text, i.def from MDX_KEYS k join MDX_INDEX i on i.text = k.text
+             WHERE k.key= :word order by k.rowid limit 1;",
+            key,
+        )
+    }
+
+    fn prefix(&self, prefix: &str, limit: usize) -> anyhow::Result<Vec<String>> {
+        let conn = get_db_connection(&self.info.file)?;
+        // 词头的主键索引按utf-8 bytes排序，U+10FFFF是最大的字符
+        let mut stmt = conn.prepare(
+            "select text from MDX_INDEX WHERE text >= :prefix and text < :end
+             order by ord limit :n;",
+        )?;
+        let rows = stmt.query_map(
+            named_params! {
+                ":prefix": prefix,
+                ":end": format!("{prefix}\u{10ffff}"),
+                ":n": limit as i64,
+            },
+            |row| row.get::<usize, String>(0),
+        )?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn scan(
+        &self,
+        filter: &mut dyn FnMut(&str) -> bool,
+        limit: usize,
+    ) -> anyhow::Result<Vec<String>> {
+        let conn = get_db_connection(&self.info.file)?;
+        let mut stmt = conn.prepare("select text from MDX_INDEX order by ord;")?;
+        let mut rows = stmt.query([])?;
+        let mut found = vec![];
+        while found.len() < limit
+            && let Some(row) = rows.next()?
+        {
+            let text: String = row.get(0)?;
+            if filter(&text) {
+                found.push(text);
+            }
+        }
+        Ok(found)
+    }
+
+    /// 依次查找: mdd索引、词典所在目录、res目录
+    fn resource(&self, path: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let conn = get_db_connection(&self.info.file)?;
+        let data = conn
+            .query_row(
+                "select data from MDD_INDEX WHERE path= :path limit 1;",
+                named_params! { ":path": normalize_resource_path(path) },
+                |row| row.get::<usize, Vec<u8>>(0),
+            )
+            .optional()?;
+        match data {
+            Some(data) => Ok(Some(data)),
+            None => dir_resource(&self.info.file, path),
+        }
+    }
+
+    fn entry_count(&self) -> anyhow::Result<usize> {
+        let conn = get_db_connection(&self.info.file)?;
+        let count = conn.query_row("select count(*) from MDX_INDEX;", [], |row| {
+            row.get::<usize, i64>(0)
+        })?;
+        Ok(count as usize)
+    }
+
+    fn search(&self, phrase: &str, limit: usize) -> anyhow::Result<Vec<SearchHit>> {
+        info!("search phrase={}, dict={}", phrase, self.info.file);
+        // 整体作为fts5短语查询，避免用户输入被解析成fts5语法
+        let fts_query = format!("\"{}\"", phrase.replace('"', "\"\""));
+        let conn = get_db_connection(&self.info.file)?;
+        let mut stmt = conn.prepare(
+            "select text, snippet(MDX_FTS, 1, char(2), char(3), '…', 16), rank
+             from MDX_FTS where MDX_FTS match :query order by rank limit :limit;",
+        )?;
+        let rows = stmt.query_map(
+            named_params! { ":query": fts_query, ":limit": limit as i64 },
+            |row| {
+                Ok(SearchHit {
+                    dictionary: self.info.name.clone(),
+                    headword: row.get(0)?,
+                    snippet: highlight(&row.get::<_, String>(1)?),
+                    rank: row.get(2)?,
+                })
+            },
+        )?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn neighbours(&self, word: &str, n: usize) -> anyhow::Result<Option<Neighbours>> {
+        let conn = get_db_connection(&self.info.file)?;
+        let found = conn
+            .query_row(
+                "select text, ord from MDX_INDEX WHERE text= :word limit 1;",
+                named_params! { ":word": word },
+                |row| Ok((row.get::<usize, String>(0)?, row.get::<usize, i64>(1)?)),
+            )
+            .optional()?;
+        let Some((headword, ord)) = found else {
+            return Ok(None);
+        };
+
+        let mut before = headwords(
+            &conn,
+            "select text from MDX_INDEX WHERE ord < :ord order by ord desc limit :n;",
+            ord,
+            n,
+        )?;
+        before.reverse();
+        let after = headwords(
+            &conn,
+            "select text from MDX_INDEX WHERE ord > :ord order by ord limit :n;",
+            ord,
+            n,
+        )?;
+        Ok(Some(Neighbours {
+            dictionary: self.info.name.clone(),
+            headword,
+            before,
+            after,
+        }))
+    }
+}
+
+fn headwords(conn: &Connection, sql: &str, ord: i64, n: usize) -> anyhow::Result<Vec<String>> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map(named_params! { ":ord": ord, ":n": n as i64 }, |row| {
+        row.get::<usize, String>(0)
+    })?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::indexing::indexing;
+    use crate::search::search;
+    use crate::stardict::IFO_MAGIC;
+
+    /// 临时目录中只有两个词条的stardict词典，res目录中有一个图片
+    fn stardict(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("mdict-rs-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("res")).unwrap();
+
+        let definitions = [
+            ("apple", "<b>apple</b> a round fruit"),
+            ("pear", "a sweet fruit, not an apple"),
+        ];
+        let mut idx = vec![];
+        let mut dict = vec![];
+        for (word, html) in definitions {
+            idx.extend_from_slice(word.as_bytes());
+            idx.push(0);
+            idx.extend_from_slice(&(dict.len() as u32).to_be_bytes());
+            idx.extend_from_slice(&(html.len() as u32).to_be_bytes());
+            dict.extend_from_slice(html.as_bytes());
+        }
+        let ifo = format!(
+            "{IFO_MAGIC}\nversion=3.0.0\nbookname={name}\nwordcount=2\nidxfilesize={}\nsametypesequence=h\n",
+            idx.len()
+        );
+        fs::write(dir.join(format!("{name}.ifo")), ifo).unwrap();
+        fs::write(dir.join(format!("{name}.idx")), idx).unwrap();
+        fs::write(dir.join(format!("{name}.dict")), dict).unwrap();
+        fs::write(dir.join("res/x.png"), b"png").unwrap();
+
+        let file = dir
+            .join(format!("{name}.ifo"))
+            .to_string_lossy()
+            .to_string();
+        indexing(&[&file], true).unwrap();
+        file
+    }
+
+    #[test]
+    fn stardict_resource() {
+        let file = stardict("resource");
+        let dict = IndexedDictionary::new(&file);
+        assert_eq!(dict.resource("x.png").unwrap(), Some(b"png".to_vec()));
+        assert_eq!(dict.resource("/x.png").unwrap(), Some(b"png".to_vec()));
+        assert_eq!(dict.resource("missing.png").unwrap(), None);
+    }
+
+    #[test]
+    fn search_ranked() {
+        let file = stardict("search");
+        let dicts: Vec<Arc<dyn Dictionary>> = vec![Arc::new(IndexedDictionary::new(&file))];
+
+        let hits = search(&dicts, "apple", None, 10).unwrap();
+        let headwords: Vec<_> = hits.iter().map(|h| h.headword.as_str()).collect();
+        assert_eq!(headwords, ["apple", "pear"]);
+        assert!(hits[0].rank <= hits[1].rank);
+        assert_eq!(hits[0].dictionary, "search");
+        assert_eq!(hits[0].snippet, "<mark>apple</mark> a round fruit");
+
+        let hits = search(&dicts, "round fruit", None, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].headword, "apple");
+
+        assert_eq!(search(&dicts, "fruit", None, 1).unwrap().len(), 1);
+        assert!(
+            search(&dicts, "fruit", Some("other"), 10)
+                .unwrap()
+                .is_empty()
+        );
+        // 引号不会被当作fts5语法
+        assert_eq!(search(&dicts, "\"apple", None, 10).unwrap().len(), 2);
+    }
+}
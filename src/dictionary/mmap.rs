@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+
+use anyhow::Context;
+use memmap2::Mmap;
+
+use crate::config::mdd_files;
+use crate::dictionary::{Dictionary, DictionaryInfo, Entry};
+use crate::mdict::mdd::Mdd;
+use crate::mdict::mdx::Mdx;
+use crate::query::Neighbours;
+use crate::resource::dir_resource;
+use crate::util::normalize_resource_path;
+
+/// 不建索引，直接通过mmap读取mdx和同名的mdd文件
+/// 打开时只解析key block得到每个record的位置，查询时才解压record所在的block
+pub struct MmapMdx {
+    info: DictionaryInfo,
+    mdx: Mdx<Mmap>,
+    // 按词头排序的records序号，用于精确和前缀查询
+    sorted: Vec<usize>,
+    mdds: Vec<Mdd<Mmap>>,
+    // mdd中的资源: normalize_resource_path后的路径 -> (mdds中的序号, 资源序号)
+    resources: HashMap<String, (usize, usize)>,
+}
+
+fn map_file(path: &Path) -> anyhow::Result<Mmap> {
+    let file = File::open(path).with_context(|| format!("open {} failed", path.display()))?;
+    // SAFETY: 词典文件只读，假定运行期间不会被其他进程修改
+    unsafe { Mmap::map(&file) }.with_context(|| format!("mmap {} failed", path.display()))
+}
+
+impl MmapMdx {
+    pub fn open(path: &Path) -> anyhow::Result<MmapMdx> {
+        let file = path.to_string_lossy();
+        let mdx = Mdx::from_buffer(map_file(path)?)
+            .with_context(|| format!("parse {} failed", path.display()))?;
+        let mut sorted: Vec<usize> = (0..mdx.len()).collect();
+        sorted.sort_by(|&a, &b| mdx.key(a).cmp(&mdx.key(b)));
+
+        let mdds = mdd_files(&file)
+            .iter()
+            .map(|mdd| {
+                Mdd::from_buffer(map_file(mdd)?)
+                    .with_context(|| format!("parse {} failed", mdd.display()))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut resources = HashMap::new();
+        for (m, mdd) in mdds.iter().enumerate() {
+            for (i, path) in mdd.paths().enumerate() {
+                resources
+                    .entry(normalize_resource_path(path))
+                    .or_insert((m, i));
+            }
+        }
+
+        Ok(MmapMdx {
+            info: DictionaryInfo::new(&file),
+            mdx,
+            sorted,
+            mdds,
+            resources,
+        })
+    }
+
+    fn text(&self, i: usize) -> &str {
+        self.mdx.key(i).unwrap_or_default()
+    }
+
+    fn entry(&self, i: usize) -> anyhow::Result<Entry> {
+        let record = self.mdx.record(i)?;
+        Ok(Entry {
+            headword: record.text.to_string(),
+            definition: record.definition,
+        })
+    }
+
+    /// sorted中第一个不小于word的位置
+    fn position(&self, word: &str) -> usize {
+        self.sorted.partition_point(|&i| self.text(i) < word)
+    }
+}
+
+impl Dictionary for MmapMdx {
+    fn info(&self) -> &DictionaryInfo {
+        &self.info
+    }
+
+    fn lookup(&self, word: &str) -> anyhow::Result<Option<Entry>> {
+        let found = self
+            .sorted
+            .get(self.position(word))
+            .filter(|&&i| self.text(i) == word);
+        found.map(|&i| self.entry(i)).transpose()
+    }
+
+    /// 没有简繁体和拼音的索引，只忽略大小写，Mdx::lookup 在按小写排序的词头中二分查找
+    fn lookup_key(&self, key: &str) -> anyhow::Result<Option<Entry>> {
+        Ok(self.mdx.lookup(key)?.map(|record| Entry {
+            headword: record.text.to_string(),
+            definition: record.definition,
+        }))
+    }
+
+    fn prefix(&self, prefix: &str, limit: usize) -> anyhow::Result<Vec<String>> {
+        let start = self.position(prefix);
+        let mut found: Vec<usize> = self.sorted[start..]
+            .iter()
+            .copied()
+            .take_while(|&i| self.text(i).starts_with(prefix))
+            .collect();
+        // 和索引一致，按词典顺序返回
+        found.sort_unstable();
+        found.truncate(limit);
+        Ok(found
+            .into_iter()
+            .map(|i| self.text(i).to_string())
+            .collect())
+    }
+
+    fn scan(
+        &self,
+        filter: &mut dyn FnMut(&str) -> bool,
+        limit: usize,
+    ) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .mdx
+            .keys()
+            .filter(|text| filter(text))
+            .take(limit)
+            .map(str::to_string)
+            .collect())
+    }
+
+    fn resource(&self, path: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        match self.resources.get(&normalize_resource_path(path)) {
+            Some(&(m, i)) => Ok(Some(self.mdds[m].resource(i)?.data)),
+            None => dir_resource(&self.info.file, path),
+        }
+    }
+
+    fn entry_count(&self) -> anyhow::Result<usize> {
+        Ok(self.mdx.len())
+    }
+
+    fn neighbours(&self, word: &str, n: usize) -> anyhow::Result<Option<Neighbours>> {
+        let Some(i) = self
+            .sorted
+            .get(self.position(word))
+            .copied()
+            .filter(|&i| self.text(i) == word)
+        else {
+            return Ok(None);
+        };
+        let texts =
+            |range: std::ops::Range<usize>| range.map(|j| self.text(j).to_string()).collect();
+        Ok(Some(Neighbours {
+            dictionary: self.info.name.clone(),
+            headword: self.text(i).to_string(),
+            before: texts(i.saturating_sub(n)..i),
+            after: texts(i + 1..(i + 1 + n).min(self.mdx.len())),
+        }))
+    }
+}
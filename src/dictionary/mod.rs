@@ -0,0 +1,103 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use serde_derive::Serialize;
+
+use crate::config::dict_name;
+use crate::query::Neighbours;
+use crate::search::SearchHit;
+
+mod indexed;
+mod mmap;
+
+pub use indexed::IndexedDictionary;
+pub use mmap::MmapMdx;
+
+/// 词典的基本信息
+#[derive(Serialize, Debug, Clone)]
+pub struct DictionaryInfo {
+    // 词典名称，即文件名去掉扩展名，用于url和选择词典
+    pub name: String,
+    pub file: String,
+    // mdx stardict dsl
+    pub format: &'static str,
+}
+
+impl DictionaryInfo {
+    pub fn new(file: &str) -> DictionaryInfo {
+        let format = match Path::new(file).extension().and_then(|e| e.to_str()) {
+            Some("ifo") => "stardict",
+            Some("dsl") => "dsl",
+            Some("dz") if file.ends_with(".dsl.dz") => "dsl",
+            _ => "mdx",
+        };
+        DictionaryInfo {
+            name: dict_name(file),
+            file: file.to_string(),
+            format,
+        }
+    }
+}
+
+/// 一个词条，释义是词典中的原始html，可能是 @@@LINK= 跳转
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub headword: String,
+    pub definition: String,
+}
+
+/// 词典的存储方式无关的查询接口，web服务和命令行都通过它查词
+/// 全文检索和前后词条不是所有存储都支持，默认没有结果
+pub trait Dictionary: Send + Sync {
+    fn info(&self) -> &DictionaryInfo;
+
+    /// 精确匹配词头
+    fn lookup(&self, word: &str) -> anyhow::Result<Option<Entry>>;
+
+    /// 按其他写法查询，如简繁体、拼音、别名、忽略大小写
+    fn lookup_key(&self, _key: &str) -> anyhow::Result<Option<Entry>> {
+        Ok(None)
+    }
+
+    /// 以prefix开头的词头，按词典顺序最多limit个
+    fn prefix(&self, prefix: &str, limit: usize) -> anyhow::Result<Vec<String>>;
+
+    /// 按词典顺序逐个检查所有词头，返回filter为true的词头，最多limit个
+    fn scan(
+        &self,
+        filter: &mut dyn FnMut(&str) -> bool,
+        limit: usize,
+    ) -> anyhow::Result<Vec<String>>;
+
+    /// 资源文件，如图片、发音、css，path是释义中的相对路径
+    fn resource(&self, path: &str) -> anyhow::Result<Option<Vec<u8>>>;
+
+    /// 词条数
+    fn entry_count(&self) -> anyhow::Result<usize>;
+
+    /// 在释义中全文检索短语
+    fn search(&self, _phrase: &str, _limit: usize) -> anyhow::Result<Vec<SearchHit>> {
+        Ok(vec![])
+    }
+
+    /// 词头在词典顺序中的前后各n个词条
+    fn neighbours(&self, _word: &str, _n: usize) -> anyhow::Result<Option<Neighbours>> {
+        Ok(None)
+    }
+}
+
+/// 配置的词典文件都使用sqlite索引，需要先调用 indexing
+pub fn open_dictionaries(files: &[&str]) -> Vec<Arc<dyn Dictionary>> {
+    files
+        .iter()
+        .map(|file| Arc::new(IndexedDictionary::new(file)) as Arc<dyn Dictionary>)
+        .collect()
+}
+
+/// 按名称查找词典
+pub fn find_dictionary<'a>(
+    dicts: &'a [Arc<dyn Dictionary>],
+    name: &str,
+) -> Option<&'a Arc<dyn Dictionary>> {
+    dicts.iter().find(|d| d.info().name == name)
+}
@@ -0,0 +1,485 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use flate2::read::MultiGzDecoder;
+
+use crate::util::escape_html;
+
+/// 一篇词条: 开头的一行或多行词头，后面以空白开头的行是释义
+struct Article {
+    headwords: Vec<String>,
+    body: Vec<String>,
+}
+
+/// ABBYY Lingvo DSL词典，释义的DSL标记转换为html
+pub struct Dsl {
+    // #NAME #INDEX_LANGUAGE #CONTENTS_LANGUAGE 等头部信息
+    pub info: HashMap<String, String>,
+    articles: Vec<Article>,
+    // 缩写和全称，来自同名的 _abrv.dsl 文件
+    abbreviations: HashMap<String, String>,
+}
+
+impl Dsl {
+    /// 打开 .dsl 或 .dsl.dz 文件，同目录下的 {name}_abrv.dsl(.dz) 作为缩写表
+    pub fn open(path: &Path) -> anyhow::Result<Dsl> {
+        let mut dsl = Dsl::parse(&read_text(path)?);
+        if let Some(abrv) = abbreviation_file(path) {
+            let abrv = Dsl::parse(&read_text(&abrv)?);
+            dsl.abbreviations = abrv
+                .articles
+                .iter()
+                .map(|a| (a.headwords[0].clone(), plain_text(&a.body.join(" "))))
+                .collect();
+        }
+        Ok(dsl)
+    }
+
+    pub fn parse(text: &str) -> Dsl {
+        let mut info = HashMap::new();
+        let mut articles: Vec<Article> = vec![];
+        let mut in_body = false;
+        for line in text.lines() {
+            if let Some(header) = line.strip_prefix('#') {
+                let (key, value) = header
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((header, ""));
+                info.insert(key.to_string(), value.trim().trim_matches('"').to_string());
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            if line.starts_with([' ', '\t']) {
+                if let Some(article) = articles.last_mut() {
+                    article.body.push(line.trim().to_string());
+                    in_body = true;
+                }
+                continue;
+            }
+            // 连续的多行词头属于同一篇词条
+            match articles.last_mut() {
+                Some(article) if !in_body => article.headwords.push(line.trim().to_string()),
+                _ => articles.push(Article {
+                    headwords: vec![line.trim().to_string()],
+                    body: vec![],
+                }),
+            }
+            in_body = false;
+        }
+        Dsl {
+            info,
+            articles,
+            abbreviations: HashMap::new(),
+        }
+    }
+
+    /// 按词头排序的(词头, html释义)，同一个词头的多篇词条合并，词头包含()中的可选部分
+    pub fn items(&self) -> Vec<(String, String)> {
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut items: Vec<(String, String)> = vec![];
+        for article in &self.articles {
+            let (_, headword) = headword_text(&article.headwords[0]);
+            let html = self.article_html(article, &headword);
+            match index.get(&headword) {
+                Some(&i) => {
+                    items[i].1.push_str("<hr/>");
+                    items[i].1.push_str(&html);
+                }
+                None => {
+                    index.insert(headword.clone(), items.len());
+                    items.push((headword, html));
+                }
+            }
+        }
+        items.sort_by_cached_key(|(headword, _)| (headword.to_lowercase(), headword.clone()));
+        items
+    }
+
+    /// (别名, 词头): 词条的其他词头，以及词头去掉()可选部分的写法
+    pub fn aliases(&self) -> Vec<(String, String)> {
+        let mut aliases = vec![];
+        for article in &self.articles {
+            let (_, headword) = headword_text(&article.headwords[0]);
+            for (i, line) in article.headwords.iter().enumerate() {
+                let (key, full) = headword_text(line);
+                for alias in [key, full] {
+                    if (i > 0 || alias != headword) && !alias.is_empty() {
+                        aliases.push((alias, headword.clone()));
+                    }
+                }
+            }
+        }
+        aliases.sort();
+        aliases.dedup();
+        aliases
+    }
+
+    fn article_html(&self, article: &Article, headword: &str) -> String {
+        let mut html = String::new();
+        for line in &article.body {
+            let (indent, line) = margin(line);
+            let line = Converter {
+                headword,
+                abbreviations: &self.abbreviations,
+            }
+            .convert(line);
+            match indent {
+                Some(n) => html.push_str(&format!(
+                    r#"<div class="m{n}" style="margin-left:{n}em">{line}</div>"#
+                )),
+                None => html.push_str(&format!("<div>{line}</div>")),
+            }
+        }
+        html
+    }
+}
+
+/// .dsl 和 .dsl.dz 的文本，编码按BOM判断，没有BOM时根据\0的位置判断是否为UTF-16
+fn read_text(path: &Path) -> anyhow::Result<String> {
+    let data = fs::read(path).with_context(|| format!("read {} failed", path.display()))?;
+    let data = if path.extension().is_some_and(|e| e == "dz") {
+        let mut plain = vec![];
+        MultiGzDecoder::new(&data[..])
+            .read_to_end(&mut plain)
+            .with_context(|| format!("gunzip {} failed", path.display()))?;
+        plain
+    } else {
+        data
+    };
+    let utf16 = |data: &[u8], le: bool| {
+        let units: Vec<u16> = data
+            .chunks_exact(2)
+            .map(|c| {
+                if le {
+                    u16::from_le_bytes([c[0], c[1]])
+                } else {
+                    u16::from_be_bytes([c[0], c[1]])
+                }
+            })
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+    Ok(match data.as_slice() {
+        [0xff, 0xfe, rest @ ..] => utf16(rest, true),
+        [0xfe, 0xff, rest @ ..] => utf16(rest, false),
+        [0xef, 0xbb, 0xbf, rest @ ..] => String::from_utf8_lossy(rest).to_string(),
+        [_, 0, ..] => utf16(&data, true),
+        [0, _, ..] => utf16(&data, false),
+        _ => String::from_utf8_lossy(&data).to_string(),
+    })
+}
+
+/// 缩写表 foo_abrv.dsl，对应 foo.dsl 或 foo.dsl.dz
+fn abbreviation_file(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_string_lossy();
+    let stem = name.strip_suffix(".dz").unwrap_or(&name);
+    let stem = stem.strip_suffix(".dsl").unwrap_or(stem);
+    [".dsl", ".dsl.dz"]
+        .iter()
+        .map(|ext| path.with_file_name(format!("{stem}_abrv{ext}")))
+        .find(|p| p.exists())
+}
+
+/// 词头行去掉转义和 {} 中不参与索引的部分，返回(索引用的词头, 展开()可选部分的词头)
+/// 如 "go (to) {the} bed" -> ("go bed", "go to bed")
+fn headword_text(line: &str) -> (String, String) {
+    let (mut key, mut full) = (String::new(), String::new());
+    let (mut braces, mut parens) = (0, 0);
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(c) = chars.next()
+                    && braces == 0
+                {
+                    full.push(c);
+                    if parens == 0 {
+                        key.push(c);
+                    }
+                }
+            }
+            '{' => braces += 1,
+            '}' => braces -= 1,
+            '(' if braces == 0 => parens += 1,
+            ')' if braces == 0 => parens -= 1,
+            c if braces == 0 => {
+                full.push(c);
+                if parens == 0 {
+                    key.push(c);
+                }
+            }
+            _ => {}
+        }
+    }
+    let normalize = |s: String| s.split_whitespace().collect::<Vec<_>>().join(" ");
+    (normalize(key), normalize(full))
+}
+
+/// 行首的 [m1]..[m9] 缩进，行尾的 [/m] 去掉
+fn margin(line: &str) -> (Option<u32>, &str) {
+    let line = line.strip_suffix("[/m]").unwrap_or(line);
+    if let Some(rest) = line.strip_prefix("[m")
+        && let Some((n, rest)) = rest.split_once(']')
+    {
+        if n.is_empty() {
+            return (Some(1), rest);
+        }
+        if let Ok(n) = n.parse() {
+            return (Some(n), rest);
+        }
+    }
+    (None, line)
+}
+
+/// 一行释义的DSL标记转换为html
+struct Converter<'a> {
+    headword: &'a str,
+    abbreviations: &'a HashMap<String, String>,
+}
+
+impl Converter<'_> {
+    fn convert(&self, line: &str) -> String {
+        let mut html = String::new();
+        let mut rest = line;
+        // 打开的标签，关闭时按栈的顺序输出
+        let mut stack: Vec<&'static str> = vec![];
+        while let Some(c) = rest.chars().next() {
+            let len = c.len_utf8();
+            match c {
+                '\\' => {
+                    let escaped = rest[len..].chars().next();
+                    if let Some(e) = escaped {
+                        html.push_str(&escape_html(&e.to_string()));
+                        rest = &rest[len + e.len_utf8()..];
+                    } else {
+                        rest = &rest[len..];
+                    }
+                    continue;
+                }
+                '~' => html.push_str(&escape_html(self.headword)),
+                '{' if rest.starts_with("{{") => {
+                    // {{注释}} 不显示
+                    rest = rest.find("}}").map_or("", |end| &rest[end + 2..]);
+                    continue;
+                }
+                '<' if rest.starts_with("<<") => {
+                    if let Some(end) = rest.find(">>") {
+                        html.push_str(&self.link(&rest[2..end]));
+                        rest = &rest[end + 2..];
+                        continue;
+                    }
+                    html.push_str("&lt;");
+                }
+                '[' => {
+                    if let Some(end) = rest.find(']') {
+                        rest = self.tag(&rest[1..end], &rest[end + 1..], &mut html, &mut stack);
+                        continue;
+                    }
+                    html.push('[');
+                }
+                c => html.push_str(&escape_html(&c.to_string())),
+            }
+            rest = &rest[len..];
+        }
+        while let Some(close) = stack.pop() {
+            html.push_str(close);
+        }
+        html
+    }
+
+    /// 处理一个 [tag]，返回剩余的文本，ref url s p 等需要内容的标签会一起消耗到结束标签
+    fn tag<'s>(
+        &self,
+        tag: &str,
+        rest: &'s str,
+        html: &mut String,
+        stack: &mut Vec<&'static str>,
+    ) -> &'s str {
+        let (name, attr) = tag.split_once(' ').unwrap_or((tag, ""));
+        if let Some(name) = name.strip_prefix('/') {
+            if let Some(close) = closing(name) {
+                // 不成对的结束标签忽略
+                if let Some(i) = stack.iter().rposition(|c| *c == close) {
+                    for c in stack.drain(i..).rev() {
+                        html.push_str(c);
+                    }
+                }
+            }
+            return rest;
+        }
+
+        if matches!(name, "ref" | "url" | "s" | "p" | "video") {
+            let end_tag = format!("[/{name}]");
+            let (content, rest) = match rest.find(&end_tag) {
+                Some(end) => (&rest[..end], &rest[end + end_tag.len()..]),
+                None => (rest, ""),
+            };
+            let content = plain_text(content);
+            html.push_str(&match name {
+                "ref" => self.link(&content),
+                "url" => format!(r#"<a href="{0}">{0}</a>"#, escape_html(&content)),
+                "p" => match self.abbreviations.get(&content) {
+                    Some(full) => format!(
+                        r#"<abbr class="p" title="{}">{}</abbr>"#,
+                        escape_html(full),
+                        escape_html(&content)
+                    ),
+                    None => format!(r#"<i class="p">{}</i>"#, escape_html(&content)),
+                },
+                _ => media(&content),
+            });
+            return rest;
+        }
+
+        let open = match name {
+            "b" | "i" | "u" | "sup" | "sub" => format!("<{name}>"),
+            "c" => format!(
+                r#"<font color="{}">"#,
+                escape_html(if attr.is_empty() {
+                    "green"
+                } else {
+                    attr.trim()
+                })
+            ),
+            "trn" | "trn1" | "!trs" | "ex" | "com" | "*" | "t" | "'" | "lang" => {
+                format!(r#"<span class="{}">"#, class(name))
+            }
+            "br" => {
+                html.push_str("<br/>");
+                return rest;
+            }
+            // m preview 等没有显示效果的标签
+            _ => return rest,
+        };
+        html.push_str(&open);
+        if let Some(close) = closing(name) {
+            stack.push(close);
+        }
+        rest
+    }
+
+    /// [ref]word[/ref] 和 <<word>> 是词条链接
+    fn link(&self, word: &str) -> String {
+        let word = plain_text(word);
+        format!(r#"<a href="entry://{0}">{0}</a>"#, escape_html(&word))
+    }
+}
+
+fn closing(name: &str) -> Option<&'static str> {
+    match name {
+        "b" => Some("</b>"),
+        "i" => Some("</i>"),
+        "u" => Some("</u>"),
+        "sup" => Some("</sup>"),
+        "sub" => Some("</sub>"),
+        "c" => Some("</font>"),
+        "trn" | "trn1" | "!trs" | "ex" | "com" | "*" | "t" | "'" | "lang" => Some("</span>"),
+        _ => None,
+    }
+}
+
+/// DSL标签对应的css class，例句为ex，和纯文本渲染识别的class一致
+fn class(name: &str) -> &'static str {
+    match name {
+        "trn" | "trn1" => "trn",
+        "!trs" => "trs",
+        "ex" => "ex",
+        "com" => "com",
+        "*" => "sec",
+        "t" => "tr",
+        "'" => "stress",
+        _ => "lang",
+    }
+}
+
+/// [s]file[/s]: 图片显示为img，其他作为发音
+fn media(file: &str) -> String {
+    let lower = file.to_lowercase();
+    let file = escape_html(file);
+    if [".png", ".jpg", ".jpeg", ".gif", ".bmp", ".svg"]
+        .iter()
+        .any(|ext| lower.ends_with(ext))
+    {
+        format!(r#"<img src="{file}"/>"#)
+    } else {
+        format!(r#"<a href="sound://{file}">{file}</a>"#)
+    }
+}
+
+/// 去掉DSL标记和转义，用于链接目标和缩写表
+fn plain_text(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    let mut in_tag = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            '[' => in_tag = true,
+            ']' if in_tag => in_tag = false,
+            c if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headwords() {
+        assert_eq!(
+            headword_text("go (to) {the} bed"),
+            ("go bed".to_string(), "go to bed".to_string())
+        );
+        assert_eq!(
+            headword_text(r"AT\&T \(company\)"),
+            ("AT&T (company)".to_string(), "AT&T (company)".to_string())
+        );
+        assert_eq!(
+            headword_text("{[b]}apple{[/b]}  pie"),
+            ("apple pie".to_string(), "apple pie".to_string())
+        );
+    }
+
+    fn convert(line: &str) -> String {
+        let abbreviations = HashMap::from([("n".to_string(), "noun".to_string())]);
+        Converter {
+            headword: "apple",
+            abbreviations: &abbreviations,
+        }
+        .convert(line)
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(
+            convert("[b]~[/b] [p]n[/p] [c red]fruit[/c]"),
+            r#"<b>apple</b> <abbr class="p" title="noun">n</abbr> <font color="red">fruit</font>"#
+        );
+        assert_eq!(
+            convert("[ex]an [i]~ a day[/ex]"),
+            r#"<span class="ex">an <i>apple a day</i></span>"#
+        );
+        assert_eq!(convert("[b]unclosed"), "<b>unclosed</b>");
+        assert_eq!(convert("[/b]stray"), "stray");
+    }
+
+    #[test]
+    fn links_media_and_escapes() {
+        assert_eq!(
+            convert("see <<pear>> or [ref]plum[/ref]"),
+            r#"see <a href="entry://pear">pear</a> or <a href="entry://plum">plum</a>"#
+        );
+        assert_eq!(
+            convert("[s]apple.wav[/s][s]apple.png[/s]"),
+            r#"<a href="sound://apple.wav">apple.wav</a><img src="apple.png"/>"#
+        );
+        assert_eq!(convert(r"a \[b\] {{note}}<c> \~"), "a [b] &lt;c&gt; ~");
+    }
+}
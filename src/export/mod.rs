@@ -0,0 +1,165 @@
+use std::fs;
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+
+use anyhow::Context;
+use serde_derive::Serialize;
+
+use crate::mdict::MdxReader;
+use crate::mdict::mdd::Mdd;
+use crate::query::{TextFormat, html_to_text};
+use crate::util::safe_relative_path;
+
+/// mdict源文件中词条之间的分隔行
+const SOURCE_SEPARATOR: &str = "</>";
+
+/// 词典导出的文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    // mdict源文件: 词头、释义、</> 各占一行
+    Source,
+    // 每行 词头\t释义
+    Tsv,
+    // 每行一个json对象
+    Jsonl,
+}
+
+impl ExportFormat {
+    /// "source" "txt" -> Source, "tsv" -> Tsv, "jsonl" "json" -> Jsonl
+    pub fn parse(name: &str) -> Option<ExportFormat> {
+        match name.trim().to_lowercase().as_str() {
+            "source" | "txt" => Some(ExportFormat::Source),
+            "tsv" => Some(ExportFormat::Tsv),
+            "jsonl" | "json" => Some(ExportFormat::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonEntry<'a> {
+    headword: &'a str,
+    definition: &'a str,
+}
+
+/// 按词典顺序导出所有词条，text不为空时释义转为纯文本或markdown，返回词条数
+/// 流式读取，每个block只解压一次
+pub fn export_mdx(
+    mdx: &mut MdxReader<impl Read + Seek>,
+    format: ExportFormat,
+    text: Option<TextFormat>,
+    out: &mut impl Write,
+) -> anyhow::Result<usize> {
+    let mut n = 0;
+    for r in mdx.records() {
+        let r = r?;
+        let definition = r.definition();
+        let def = definition.trim_end_matches('\0');
+        let def = match text {
+            // 跳转词条保持原样，导入时仍然是跳转
+            Some(format) if !def.starts_with("@@@LINK=") => html_to_text(def, format),
+            _ => def.trim_end().to_string(),
+        };
+        match format {
+            ExportFormat::Source => {
+                writeln!(out, "{}\n{}\n{SOURCE_SEPARATOR}", r.text, def)?;
+            }
+            ExportFormat::Tsv => {
+                writeln!(out, "{}\t{}", tsv_escape(&r.text), tsv_escape(&def))?;
+            }
+            ExportFormat::Jsonl => {
+                let entry = JsonEntry {
+                    headword: &r.text,
+                    definition: &def,
+                };
+                serde_json::to_writer(&mut *out, &entry)?;
+                writeln!(out)?;
+            }
+        }
+        n += 1;
+    }
+    out.flush()?;
+    Ok(n)
+}
+
+/// 解析mdict源文件，返回(词头,释义)列表，释义中的多行以\r\n连接
+pub fn parse_source(source: &str) -> Vec<(String, String)> {
+    let mut entries = vec![];
+    let mut lines = vec![];
+    for line in source.trim_start_matches('\u{feff}').lines() {
+        if line.trim_end() != SOURCE_SEPARATOR {
+            lines.push(line);
+            continue;
+        }
+        // 词条之间可能有空行
+        let mut entry = lines.drain(..).skip_while(|l| l.trim().is_empty());
+        if let Some(headword) = entry.next() {
+            let definition = entry.collect::<Vec<_>>().join("\r\n");
+            entries.push((headword.trim().to_string(), definition));
+        }
+    }
+    entries
+}
+
+/// tsv字段中的反斜杠、tab和换行转义为 \\ \t \n \r
+fn tsv_escape(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// mdd中的资源文件按原路径写入目录，返回文件数，不安全的路径会被跳过
+pub fn extract_mdd(mdd: &Mdd, dir: &Path) -> anyhow::Result<usize> {
+    let mut n = 0;
+    for r in mdd.items() {
+        let r = r?;
+        let Some(relative) = safe_relative_path(r.path.replace('\\', "/").trim_start_matches('/'))
+        else {
+            tracing::warn!("skip resource with unsafe path: {}", r.path);
+            continue;
+        };
+        let target = dir.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("create dir {} failed", parent.display()))?;
+        }
+        fs::write(&target, &r.data)
+            .with_context(|| format!("write {} failed", target.display()))?;
+        n += 1;
+    }
+    Ok(n)
+}
+
+/// 递归读取目录中的文件，返回mdd中的(资源路径,文件内容)，如 "img/a.png" -> "\img\a.png"
+pub fn collect_resources(dir: &Path) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+    let mut resources = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        let entries = fs::read_dir(&current)
+            .with_context(|| format!("read dir {} failed", current.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let relative = path.strip_prefix(dir)?;
+            let key: String = relative
+                .components()
+                .map(|c| format!("\\{}", c.as_os_str().to_string_lossy()))
+                .collect();
+            let data =
+                fs::read(&path).with_context(|| format!("read {} failed", path.display()))?;
+            resources.push((key, data));
+        }
+    }
+    Ok(resources)
+}
@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use lol_html::{RewriteStrSettings, element, rewrite_str};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+
+use crate::dictionary::{Dictionary, find_dictionary};
+use crate::history;
+use crate::query::{lookup_all, render};
+use crate::util::escape_html;
+
+/// 点击发音链接时直接播放，GoldenDict的网页中没有index.js
+const SOUND_SCRIPT: &str = r#"<script>
+document.addEventListener('click', function (e) {
+    var a = e.target.closest && e.target.closest('a[data-sound]');
+    if (a) {
+        e.preventDefault();
+        new Audio(a.href).play();
+    }
+});
+</script>"#;
+
+/// GoldenDict "网站" 词典来源使用的单个词典页面，如 http://localhost:8181/gd/牛津高阶8/%GDWORD%
+/// 页面是完整的html文档，资源、css和词条链接都改为 origin 开头的绝对地址，
+/// 词条链接跳到同一词典的 /gd/ 页面，词典不存在时返回Ok(None)，没有查到时释义为空
+/// 查询记录在user的历史中
+pub fn render_page(
+    dicts: &[Arc<dyn Dictionary>],
+    dict: &str,
+    word: &str,
+    origin: &str,
+    user: &str,
+) -> anyhow::Result<Option<(bool, String)>> {
+    if find_dictionary(dicts, dict).is_none() {
+        return Ok(None);
+    }
+    let results = lookup_all(dicts, word, Some(dict))?;
+    history::record_lookup(user, word, &results);
+    let body = match results.first() {
+        Some(result) => absolute_urls(&render(dicts, result), dict, origin)?,
+        None => String::new(),
+    };
+    let page = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{word} - {dict}</title>
+<base href="{origin}/">
+</head>
+<body>
+{body}
+{SOUND_SCRIPT}
+</body>
+</html>
+"#,
+        word = escape_html(word),
+        dict = escape_html(dict),
+        origin = escape_html(origin),
+    );
+    Ok(Some((!results.is_empty(), page)))
+}
+
+/// 以/开头的站内地址加上origin，/word/{word} 改为 /gd/{dict}/{word}
+fn absolute_urls(html: &str, dict: &str, origin: &str) -> anyhow::Result<String> {
+    let gd_prefix = format!(
+        "{origin}/gd/{}/",
+        utf8_percent_encode(dict, NON_ALPHANUMERIC)
+    );
+    let absolute = |url: &str| -> Option<String> {
+        if !url.starts_with('/') || url.starts_with("//") {
+            return None;
+        }
+        Some(match url.strip_prefix("/word/") {
+            Some(word) => format!("{gd_prefix}{word}"),
+            None => format!("{origin}{url}"),
+        })
+    };
+    let rewritten = rewrite_str(
+        html,
+        RewriteStrSettings::new()
+            .append_element_content_handler(element!("a[href], link[href]", |el| {
+                let href = el.get_attribute("href").unwrap_or_default();
+                if let Some(url) = absolute(&href) {
+                    el.set_attribute("href", &url)?;
+                }
+                Ok(())
+            }))
+            .append_element_content_handler(element!("[src]", |el| {
+                let src = el.get_attribute("src").unwrap_or_default();
+                if let Some(url) = absolute(&src) {
+                    el.set_attribute("src", &url)?;
+                }
+                Ok(())
+            })),
+    )?;
+    Ok(rewritten)
+}
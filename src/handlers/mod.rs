@@ -1,27 +1,264 @@
-use crate::lucky;
-use crate::query::query;
+use std::sync::Arc;
+
+use mdict_rs::audio::playable;
+use mdict_rs::config::static_path;
+use mdict_rs::dictionary::{Dictionary, find_dictionary};
+use mdict_rs::goldendict;
+use mdict_rs::history;
+use mdict_rs::lucky;
+use mdict_rs::query::{Neighbours, lookup_all, neighbours, query, render};
+use mdict_rs::resource::find_resource;
+use mdict_rs::search::{SearchHit, search};
+use mdict_rs::style::scoped_css;
+use mdict_rs::util::escape_html;
 use serde_derive::Deserialize;
 
-use axum::{extract::Form, response::Response};
+use crate::api::history::HistoryUser;
+
+use axum::{
+    Json,
+    extract::{Form, Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{Html, IntoResponse, Response},
+};
 
 #[derive(Deserialize, Debug)]
 pub struct QueryForm {
     word: String,
 }
 
-pub(crate) async fn handle_query(Form(params): Form<QueryForm>) -> Response {
-    let result = query(params.word);
+pub(crate) async fn handle_query(
+    State(dicts): State<Vec<Arc<dyn Dictionary>>>,
+    HistoryUser(user): HistoryUser,
+    Form(params): Form<QueryForm>,
+) -> Response {
+    let word = params.word.trim();
+    let result = match lookup_all(&dicts, word, None) {
+        Ok(results) => {
+            history::record_lookup(&user, word, &results);
+            match results.first() {
+                Some(result) => render(&dicts, result),
+                None => "not found".to_string(),
+            }
+        }
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
     axum::http::Response::builder()
-        .header("Content-Type", "text/plain")
+        .header("Content-Type", "text/html; charset=utf-8")
         .body(result.into())
         .unwrap()
 }
 
-pub(crate) async fn handle_lucky() -> Response {
+#[derive(Deserialize, Debug)]
+pub struct SearchParams {
+    q: String,
+    // 只检索某个词典，词典名为mdx文件名去掉扩展名
+    dict: Option<String>,
+    limit: Option<usize>,
+}
+
+/// 释义全文检索 GET /search?q=in the nick of time&dict=牛津高阶8
+pub(crate) async fn handle_search(
+    State(dicts): State<Vec<Arc<dyn Dictionary>>>,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<Vec<SearchHit>>, (StatusCode, String)> {
+    let limit = params.limit.unwrap_or(50).min(500);
+    search(&dicts, &params.q, params.dict.as_deref(), limit)
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NeighboursParams {
+    word: String,
+    dict: Option<String>,
+    n: Option<usize>,
+}
+
+/// 词典顺序中前后的词条 GET /neighbours?word=apple&n=10
+pub(crate) async fn handle_neighbours(
+    State(dicts): State<Vec<Arc<dyn Dictionary>>>,
+    Query(params): Query<NeighboursParams>,
+) -> Result<Json<Neighbours>, (StatusCode, String)> {
+    let n = params.n.unwrap_or(10).min(100);
+    match neighbours(&dicts, params.word.trim(), params.dict.as_deref(), n) {
+        Ok(Some(result)) => Ok(Json(result)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "not found".to_string())),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+pub(crate) async fn handle_lucky(State(dicts): State<Vec<Arc<dyn Dictionary>>>) -> Response {
     let word = lucky::lucky_word();
-    let result = query(word);
+    let result = match query(&dicts, word) {
+        Ok(result) => result,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
     axum::http::Response::builder()
-        .header("Content-Type", "text/plain")
+        .header("Content-Type", "text/html; charset=utf-8")
         .body(result.into())
         .unwrap()
 }
+
+/// 可收藏和分享的查询页面 GET /word/{headword}，服务端直接渲染查询结果
+pub(crate) async fn handle_word(
+    State(dicts): State<Vec<Arc<dyn Dictionary>>>,
+    HistoryUser(user): HistoryUser,
+    Path(headword): Path<String>,
+) -> Response {
+    let word = headword.trim();
+    let results = lookup_all(&dicts, word, None);
+    if let Ok(results) = &results {
+        history::record_lookup(&user, word, results);
+    }
+    let (status, result) = match results {
+        Ok(results) if !results.is_empty() => (StatusCode::OK, render(&dicts, &results[0])),
+        Ok(_) => (StatusCode::NOT_FOUND, "not found".to_string()),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, escape_html(&e.to_string())),
+    };
+    match render_page(word, &result) {
+        Ok(page) => (status, Html(page)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// 把查询结果填入index.html: 结果放在 <!--mdx-resp--> 标记之间，输入框和标题填入查询词
+fn render_page(word: &str, result: &str) -> anyhow::Result<String> {
+    let template = std::fs::read_to_string(static_path()?.join("index.html"))?;
+    let (head, rest) = template
+        .split_once("<!--mdx-resp-->")
+        .ok_or_else(|| anyhow::anyhow!("index.html has no <!--mdx-resp--> marker"))?;
+    let (_, tail) = rest
+        .split_once("<!--/mdx-resp-->")
+        .ok_or_else(|| anyhow::anyhow!("index.html has no <!--/mdx-resp--> marker"))?;
+
+    let escaped = escape_html(word);
+    let head = head
+        .replacen(
+            "<title>Moe词典</title>",
+            &format!("<title>{escaped} - Moe词典</title>"),
+            1,
+        )
+        .replacen(
+            r#"id="word""#,
+            &format!(r#"id="word" value="{escaped}""#),
+            1,
+        );
+    Ok(format!("{head}{result}{tail}"))
+}
+
+/// GoldenDict的网站词典来源 GET /gd/{dict}/{word}，返回单个词典的完整html页面
+pub(crate) async fn handle_goldendict(
+    State(dicts): State<Vec<Arc<dyn Dictionary>>>,
+    HistoryUser(user): HistoryUser,
+    Path((dict, word)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("localhost:8181");
+    let origin = format!("http://{host}");
+    match goldendict::render_page(&dicts, &dict, word.trim(), &origin, &user) {
+        Ok(Some((true, page))) => Html(page).into_response(),
+        Ok(Some((false, page))) => (StatusCode::NOT_FOUND, Html(page)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// OpenSearch描述文件，浏览器可以把本站添加为搜索引擎
+pub(crate) async fn handle_opensearch(headers: HeaderMap) -> Response {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("localhost:8181");
+    let body = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<OpenSearchDescription xmlns="http://a9.com/-/spec/opensearch/1.1/">
+    <ShortName>Moe词典</ShortName>
+    <Description>ZhiMoe词典</Description>
+    <InputEncoding>UTF-8</InputEncoding>
+    <Image width="16" height="16" type="image/x-icon">http://{host}/favicon.ico</Image>
+    <Url type="text/html" method="get" template="http://{host}/word/{{searchTerms}}"/>
+</OpenSearchDescription>
+"#,
+        host = escape_html(host)
+    );
+    (
+        [(
+            header::CONTENT_TYPE,
+            "application/opensearchdescription+xml",
+        )],
+        body,
+    )
+        .into_response()
+}
+
+/// 词典资源文件 GET /resource/{dict}/{*path}，如图片、发音、css
+pub(crate) async fn handle_resource(
+    State(dicts): State<Vec<Arc<dyn Dictionary>>>,
+    Path((dict, path)): Path<(String, String)>,
+) -> Response {
+    let Some(dict) = find_dictionary(&dicts, &dict) else {
+        return (StatusCode::NOT_FOUND, "not found").into_response();
+    };
+    match find_resource(dict.as_ref(), &path) {
+        Ok(Some(data)) => {
+            let mime = mime_guess::from_path(&path).first_or_octet_stream();
+            (
+                [
+                    (header::CONTENT_TYPE, mime.to_string()),
+                    (header::CACHE_CONTROL, "public, max-age=86400".to_string()),
+                ],
+                data,
+            )
+                .into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// 发音 GET /audio/{dict}/{*path}，speex(.spx)转码为wav，其他格式原样返回
+pub(crate) async fn handle_audio(
+    State(dicts): State<Vec<Arc<dyn Dictionary>>>,
+    Path((dict, path)): Path<(String, String)>,
+) -> Response {
+    let Some(dict) = find_dictionary(&dicts, &dict).cloned() else {
+        return (StatusCode::NOT_FOUND, "not found").into_response();
+    };
+    let result = tokio::task::spawn_blocking(move || {
+        playable(&dict.info().name, &path, || {
+            find_resource(dict.as_ref(), &path)
+        })
+    })
+    .await;
+    match result {
+        Ok(Ok(Some(audio))) => (
+            [
+                (header::CONTENT_TYPE, audio.content_type),
+                (header::CACHE_CONTROL, "public, max-age=86400".to_string()),
+            ],
+            audio.data.to_vec(),
+        )
+            .into_response(),
+        Ok(Ok(None)) => (StatusCode::NOT_FOUND, "not found").into_response(),
+        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// 词典的css GET /style/{dict}，所有规则都限定在该词典的结果容器内
+pub(crate) async fn handle_style(
+    State(dicts): State<Vec<Arc<dyn Dictionary>>>,
+    Path(dict): Path<String>,
+) -> Response {
+    let Some(dict) = find_dictionary(&dicts, &dict) else {
+        return (StatusCode::NOT_FOUND, "not found").into_response();
+    };
+    match scoped_css(dict.as_ref()) {
+        Ok(css) => ([(header::CONTENT_TYPE, "text/css; charset=utf-8")], css).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
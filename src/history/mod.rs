@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{Row, ToSql, params};
+use serde_derive::Serialize;
+use tracing::warn;
+
+use crate::config::HISTORY_DB;
+use crate::query::LookupResult;
+use crate::util::unix_now;
+
+/// 多个匹配词典名之间的分隔符
+const DICT_SEPARATOR: char = '\n';
+
+/// 查询历史数据库的连接池，第一次使用时创建表
+static HISTORY_POOL: LazyLock<Pool<SqliteConnectionManager>> = LazyLock::new(|| {
+    let manager = SqliteConnectionManager::file(HISTORY_DB).with_init(|conn| {
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "busy_timeout", "5000")?;
+        conn.execute_batch(
+            "create table if not exists HISTORY(
+                id integer primary key,
+                word text not null,
+                time integer not null,
+                dictionaries text not null,
+                hit integer not null,
+                user text not null default ''
+            );",
+        )?;
+        // 早期的表没有user列，原有的记录属于匿名用户
+        let has_user: bool = conn.query_row(
+            "select count(*) > 0 from pragma_table_info('HISTORY') where name = 'user'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_user {
+            conn.execute_batch("alter table HISTORY add column user text not null default ''")?;
+        }
+        conn.execute_batch(
+            "create index if not exists HISTORY_WORD on HISTORY(word collate nocase);
+            create index if not exists HISTORY_USER_TIME on HISTORY(user, time);",
+        )
+    });
+    Pool::builder()
+        .max_size(4)
+        .build(manager)
+        .unwrap_or_else(|e| panic!("Failed to create connection pool for {HISTORY_DB}: {e}"))
+});
+
+/// 一次查询记录，time为unix时间戳(秒)
+#[derive(Serialize, Debug)]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub word: String,
+    pub time: i64,
+    // 查到结果的词典，按词典顺序
+    pub dictionaries: Vec<String>,
+    pub hit: bool,
+}
+
+/// 查询历史的过滤条件，user总是过滤，其他条件都为None时不过滤
+#[derive(Debug, Default)]
+pub struct HistoryFilter {
+    // 历史所属的用户，空字符串为匿名用户
+    pub user: String,
+    // 查询词，不区分大小写
+    pub word: Option<String>,
+    // 在该词典中查到结果
+    pub dict: Option<String>,
+    pub hit: Option<bool>,
+    // 时间范围 [since, until)
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+}
+
+/// 一页查询历史，按时间倒序
+#[derive(Serialize, Debug)]
+pub struct HistoryPage {
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub items: Vec<HistoryEntry>,
+}
+
+/// 一个查询词的次数
+#[derive(Serialize, Debug)]
+pub struct WordCount {
+    pub word: String,
+    pub count: usize,
+    pub last_time: i64,
+}
+
+/// 查询频率统计
+#[derive(Serialize, Debug)]
+pub struct HistoryStats {
+    pub lookups: usize,
+    pub words: usize,
+    pub hits: usize,
+    pub misses: usize,
+    // 查询次数最多的词
+    pub top_words: Vec<WordCount>,
+    // 每个词典查到结果的次数，按次数倒序
+    pub dictionaries: Vec<DictCount>,
+}
+
+/// 一个词典查到结果的次数
+#[derive(Serialize, Debug)]
+pub struct DictCount {
+    pub dictionary: String,
+    pub count: usize,
+}
+
+/// 过滤条件对应的where子句，参数依次为 word dict hit since until user
+const FILTER_SQL: &str = "user = ?6
+    and (?1 is null or word = ?1 collate nocase)
+    and (?2 is null or instr(char(10) || dictionaries || char(10), char(10) || ?2 || char(10)) > 0)
+    and (?3 is null or hit = ?3)
+    and (?4 is null or time >= ?4)
+    and (?5 is null or time < ?5)";
+
+impl HistoryFilter {
+    fn params(&self) -> [&dyn ToSql; 6] {
+        [
+            &self.word,
+            &self.dict,
+            &self.hit,
+            &self.since,
+            &self.until,
+            &self.user,
+        ]
+    }
+}
+
+fn connection() -> anyhow::Result<r2d2::PooledConnection<SqliteConnectionManager>> {
+    HISTORY_POOL
+        .get()
+        .map_err(|e| anyhow::anyhow!("Failed to get history connection: {}", e))
+}
+
+/// 记录user的一次查询，dictionaries为空表示没有查到
+pub fn record(user: &str, word: &str, dictionaries: &[String]) -> anyhow::Result<()> {
+    let joined = dictionaries.join(&DICT_SEPARATOR.to_string());
+    connection()?.execute(
+        "insert into HISTORY(word, time, dictionaries, hit, user) values (?1, ?2, ?3, ?4, ?5)",
+        params![word, unix_now(), joined, !dictionaries.is_empty(), user],
+    )?;
+    Ok(())
+}
+
+/// 记录查询结果，记录失败只打日志，不影响查询
+pub fn record_lookup(user: &str, word: &str, results: &[LookupResult]) {
+    let dictionaries: Vec<String> = results.iter().map(|r| r.dictionary.clone()).collect();
+    if let Err(e) = record(user, word, &dictionaries) {
+        warn!("record history of {} failed: {}", word, e);
+    }
+}
+
+fn entry(row: &Row) -> rusqlite::Result<HistoryEntry> {
+    let dictionaries: String = row.get(3)?;
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        word: row.get(1)?,
+        time: row.get(2)?,
+        dictionaries: dictionaries
+            .split(DICT_SEPARATOR)
+            .filter(|d| !d.is_empty())
+            .map(str::to_string)
+            .collect(),
+        hit: row.get(4)?,
+    })
+}
+
+/// 按时间倒序分页查询历史
+pub fn list(filter: &HistoryFilter, offset: usize, limit: usize) -> anyhow::Result<HistoryPage> {
+    let conn = connection()?;
+    let total: usize = conn.query_row(
+        &format!("select count(*) from HISTORY where {FILTER_SQL}"),
+        filter.params(),
+        |row| row.get(0),
+    )?;
+    let mut stmt = conn.prepare(&format!(
+        "select id, word, time, dictionaries, hit from HISTORY where {FILTER_SQL}
+        order by time desc, id desc limit ?7 offset ?8"
+    ))?;
+    let (sql_limit, sql_offset) = (to_sql_int(limit), to_sql_int(offset));
+    let mut params = filter.params().to_vec();
+    params.extend([&sql_limit as &dyn ToSql, &sql_offset]);
+    let items = stmt
+        .query_map(&params[..], entry)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(HistoryPage {
+        total,
+        offset,
+        limit,
+        items,
+    })
+}
+
+/// 过滤条件下的全部历史，按时间顺序，用于导出
+pub fn export(filter: &HistoryFilter) -> anyhow::Result<Vec<HistoryEntry>> {
+    let conn = connection()?;
+    let mut stmt = conn.prepare(&format!(
+        "select id, word, time, dictionaries, hit from HISTORY where {FILTER_SQL}
+        order by time, id"
+    ))?;
+    let items = stmt
+        .query_map(filter.params(), entry)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(items)
+}
+
+/// user最近查询过的不同的词，最近的在前
+pub fn recent(user: &str, limit: usize) -> anyhow::Result<Vec<String>> {
+    let conn = connection()?;
+    let mut stmt = conn.prepare(
+        "select word from HISTORY where user = ?1 group by word collate nocase
+        order by max(time) desc, max(id) desc limit ?2",
+    )?;
+    let words = stmt
+        .query_map(params![user, to_sql_int(limit)], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(words)
+}
+
+/// 查询次数统计，top为最多返回的高频词数量
+pub fn stats(filter: &HistoryFilter, top: usize) -> anyhow::Result<HistoryStats> {
+    let conn = connection()?;
+    let (lookups, words, hits): (usize, usize, usize) = conn.query_row(
+        &format!(
+            "select count(*), count(distinct lower(word)), coalesce(sum(hit), 0)
+            from HISTORY where {FILTER_SQL}"
+        ),
+        filter.params(),
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+
+    let mut stmt = conn.prepare(&format!(
+        "select word, count(*) as n, max(time) from HISTORY where {FILTER_SQL}
+        group by word collate nocase order by n desc, max(time) desc limit ?7"
+    ))?;
+    let sql_top = to_sql_int(top);
+    let mut params = filter.params().to_vec();
+    params.push(&sql_top);
+    let top_words = stmt
+        .query_map(&params[..], |row| {
+            Ok(WordCount {
+                word: row.get(0)?,
+                count: row.get(1)?,
+                last_time: row.get(2)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut stmt = conn.prepare(&format!(
+        "select dictionaries from HISTORY where {FILTER_SQL} and hit = 1"
+    ))?;
+    let mut rows = stmt.query(filter.params())?;
+    while let Some(row) = rows.next()? {
+        let dictionaries: String = row.get(0)?;
+        for dict in dictionaries.split(DICT_SEPARATOR).filter(|d| !d.is_empty()) {
+            *counts.entry(dict.to_string()).or_default() += 1;
+        }
+    }
+    let mut dictionaries: Vec<DictCount> = counts
+        .into_iter()
+        .map(|(dictionary, count)| DictCount { dictionary, count })
+        .collect();
+    dictionaries.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.dictionary.cmp(&b.dictionary))
+    });
+
+    Ok(HistoryStats {
+        lookups,
+        words,
+        hits,
+        misses: lookups - hits,
+        top_words,
+        dictionaries,
+    })
+}
+
+/// 删除过滤条件下的历史，返回删除的条数
+pub fn clear(filter: &HistoryFilter) -> anyhow::Result<usize> {
+    let deleted = connection()?.execute(
+        &format!("delete from HISTORY where {FILTER_SQL}"),
+        filter.params(),
+    )?;
+    Ok(deleted)
+}
+
+/// sqlite的整数是i64，超出时取最大值
+fn to_sql_int(n: usize) -> i64 {
+    i64::try_from(n).unwrap_or(i64::MAX)
+}
+
+/// 导出为csv: word,time,dictionaries,hit，多个词典用;分隔
+pub fn to_csv(entries: &[HistoryEntry]) -> String {
+    let mut csv = String::from("word,time,dictionaries,hit\n");
+    for e in entries {
+        csv.push_str(&format!(
+            "{},{},{},{}\n",
+            csv_field(&e.word),
+            e.time,
+            csv_field(&e.dictionaries.join(";")),
+            e.hit
+        ));
+    }
+    csv
+}
+
+/// 包含逗号、引号或换行的字段用引号包裹，引号转义为两个引号
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -1,59 +1,229 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::Context;
-use rusqlite::{params, Connection};
+use rusqlite::{Connection, params};
 
-use crate::mdict::mdx::Mdx;
+use crate::config::mdd_files;
+use crate::dsl::Dsl;
+use crate::mdict::MdxReader;
+use crate::normalize::headword_keys;
+use crate::query::{TextFormat, html_to_text};
+use crate::stardict::StarDict;
+use crate::util::normalize_resource_path;
 use tracing::info;
 
+/// 索引表结构版本，保存在sqlite的 user_version 中，表结构变化时加1，旧版本的db文件会被重建
+pub(crate) const INDEX_VERSION: i32 = 6;
+
 /// indexing all mdx files into db
-pub(crate) fn indexing(files: &[&str], reindex: bool) -> anyhow::Result<()> {
+/// 索引失败的db没有写入 user_version，下次启动时会被重建
+pub fn indexing(files: &[&str], reindex: bool) -> anyhow::Result<()> {
     for file in files {
         let db_file_name = format!("{}{}", file, ".db");
         let db_path = PathBuf::from(&db_file_name);
         if db_path.exists() {
-            if reindex {
-                fs::remove_file(&db_file_name).expect("remove old db file error");
+            if reindex || index_version(&db_file_name)? != INDEX_VERSION {
+                fs::remove_file(&db_file_name)
+                    .with_context(|| format!("remove old db file {db_file_name} failed"))?;
                 info!("old db file:{} removed", &db_file_name);
-                mdx_to_sqlite(file).expect("indexing failed");
+                index_file(file).with_context(|| format!("indexing {file} failed"))?;
             }
         } else {
-            mdx_to_sqlite(file).expect("indexing failed");
+            index_file(file).with_context(|| format!("indexing {file} failed"))?;
         }
     }
 
     Ok(())
 }
 
+/// 按扩展名选择词典格式，同时索引同名的mdd资源
+/// 所有步骤都成功后才写入索引版本
+fn index_file(file: &str) -> anyhow::Result<()> {
+    match Path::new(file).extension().and_then(|e| e.to_str()) {
+        Some("ifo") => stardict_to_sqlite(file)?,
+        Some("dsl") => dsl_to_sqlite(file)?,
+        Some("dz") if file.ends_with(".dsl.dz") => dsl_to_sqlite(file)?,
+        _ => mdx_to_sqlite(file)?,
+    }
+    // 没有mdd的词典也有空的MDD_INDEX表，资源查询回退到词典目录和res目录
+    mdd_to_sqlite(file)?;
+    let conn = Connection::open(format!("{}{}", file, ".db"))?;
+    conn.pragma_update(None, "user_version", INDEX_VERSION)?;
+    Ok(())
+}
+
+/// 已有db文件的索引版本
+fn index_version(db_file: &str) -> anyhow::Result<i32> {
+    let conn = Connection::open(db_file)?;
+    let version = conn.query_row("pragma user_version", [], |row| row.get(0))?;
+    Ok(version)
+}
+
 /// mdx entries and definition to sqlite table
 pub(crate) fn mdx_to_sqlite(file: &str) -> anyhow::Result<()> {
+    // 流式读取，每个block只解压一次，大词典也不需要整个读入内存
+    let mut mdx = MdxReader::open(file).with_context(|| format!("parse {file} failed"))?;
+    let entries = mdx.records().map(|r| {
+        let r = r?;
+        let definition = r.definition();
+        Ok((r.text, definition))
+    });
+    entries_to_sqlite(file, entries, std::iter::empty())
+}
+
+/// stardict词典写入同样的表，.syn中的同义词作为MDX_KEYS中的别名
+pub(crate) fn stardict_to_sqlite(file: &str) -> anyhow::Result<()> {
+    let dict = StarDict::open(Path::new(file))?;
+    entries_to_sqlite(file, dict.items(), dict.synonyms())
+}
+
+/// dsl词典写入同样的表，词条的其他词头和()中可选部分的写法作为别名
+pub(crate) fn dsl_to_sqlite(file: &str) -> anyhow::Result<()> {
+    let dsl = Dsl::open(Path::new(file))?;
+    let aliases = dsl.aliases();
+    entries_to_sqlite(
+        file,
+        dsl.items().into_iter().map(Ok),
+        aliases.iter().map(|(a, t)| (a.as_str(), t.as_str())),
+    )
+}
+
+/// 词条写入db: MDX_INDEX 词头和释义, MDX_FTS 释义全文索引, MDX_KEYS 词头的别名
+/// aliases是词典自带的(别名,词头)，如stardict的同义词
+fn entries_to_sqlite<'a>(
+    file: &str,
+    entries: impl Iterator<Item = anyhow::Result<(String, String)>>,
+    aliases: impl Iterator<Item = (&'a str, &'a str)>,
+) -> anyhow::Result<()> {
     let db_file = format!("{}{}", file, ".db");
     let mut conn = Connection::open(&db_file)?;
-    let mdx = Mdx::new(&fs::read(file)?);
 
     conn.execute(
         "create table if not exists MDX_INDEX (
                 text text primary key not null ,
-                def text not null
+                def text not null ,
+                ord integer not null
+         )",
+        params![],
+    )
+    .with_context(|| "create table failed")?;
+
+    // 释义的纯文本全文索引，用于反查包含某个短语的词条
+    conn.execute(
+        "create virtual table if not exists MDX_FTS using fts5(
+                text unindexed,
+                content
          )",
         params![],
     )
-        .with_context(|| "create table failed")?;
+    .with_context(|| "create fts table failed")?;
+
+    // 中文词头的简繁体写法和拼音，查询时作为别名
+    conn.execute(
+        "create table if not exists MDX_KEYS (
+                key text not null ,
+                text text not null
+         )",
+        params![],
+    )
+    .with_context(|| "create keys table failed")?;
 
     let tx = conn
         .transaction()
         .with_context(|| "get transaction from connection failed")?;
 
-    for r in mdx.items() {
+    // ord是词条在词典中的序号，mdx的key block和stardict的idx本身是按词典顺序排好的，dsl按词头排序
+    for (ord, entry) in entries.enumerate() {
+        let (text, definition) = entry?;
         tx.execute(
-            "insert or replace into MDX_INDEX values (?,?)",
-            params![r.text, r.definition],
+            "insert or replace into MDX_INDEX values (?,?,?)",
+            params![text, definition, ord as i64],
         )
-            .with_context(|| "insert MDX_INDEX table error")?;
+        .with_context(|| "insert MDX_INDEX table error")?;
+
+        for key in headword_keys(&text) {
+            tx.execute(
+                "insert into MDX_KEYS (key, text) values (?,?)",
+                params![key, text],
+            )
+            .with_context(|| "insert MDX_KEYS table error")?;
+        }
+
+        // @@@LINK= 是跳转词条，没有释义内容
+        if !definition.starts_with("@@@LINK=") {
+            tx.execute(
+                "insert into MDX_FTS (text, content) values (?,?)",
+                params![text, index_text(definition.trim_end_matches('\0'))],
+            )
+            .with_context(|| "insert MDX_FTS table error")?;
+        }
+    }
+    for (alias, text) in aliases {
+        tx.execute(
+            "insert into MDX_KEYS (key, text) values (?,?)",
+            params![alias, text],
+        )
+        .with_context(|| "insert MDX_KEYS table error")?;
     }
+    tx.execute(
+        "create index if not exists MDX_KEYS_KEY on MDX_KEYS (key)",
+        params![],
+    )
+    .with_context(|| "create MDX_KEYS index error")?;
+    tx.execute(
+        "create index if not exists MDX_INDEX_ORD on MDX_INDEX (ord)",
+        params![],
+    )
+    .with_context(|| "create MDX_INDEX ord index error")?;
     tx.commit().with_context(|| "transaction commit error")?;
-    conn.close().expect("close db connection failed");
+    conn.close()
+        .map_err(|(_, e)| e)
+        .with_context(|| "close db connection failed")?;
     Ok(())
 }
 
+/// 释义的纯文本合并为一行写入全文索引，检索结果的片段中没有换行
+fn index_text(definition: &str) -> String {
+    html_to_text(definition, TextFormat::Text)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 词典同名的mdd资源文件写入同一个db的MDD_INDEX表，path为统一格式的资源路径
+pub(crate) fn mdd_to_sqlite(file: &str) -> anyhow::Result<()> {
+    let db_file = format!("{}{}", file, ".db");
+    let mut conn = Connection::open(&db_file)?;
+
+    conn.execute(
+        "create table if not exists MDD_INDEX (
+                path text primary key not null ,
+                data blob not null
+         )",
+        params![],
+    )
+    .with_context(|| "create mdd table failed")?;
+
+    for mdd_file in mdd_files(file) {
+        info!("indexing mdd file:{}", mdd_file.display());
+        let mut mdd = MdxReader::open_mdd(&mdd_file)
+            .with_context(|| format!("parse {} failed", mdd_file.display()))?;
+        let tx = conn
+            .transaction()
+            .with_context(|| "get transaction from connection failed")?;
+        for r in mdd.records() {
+            let r = r?;
+            tx.execute(
+                "insert or replace into MDD_INDEX values (?,?)",
+                params![normalize_resource_path(&r.text), r.data],
+            )
+            .with_context(|| "insert MDD_INDEX table error")?;
+        }
+        tx.commit().with_context(|| "transaction commit error")?;
+    }
+    conn.close()
+        .map_err(|(_, e)| e)
+        .with_context(|| "close db connection failed")?;
+    Ok(())
+}
@@ -0,0 +1,24 @@
+//! mdict词典的解析、索引和查询，供web服务和命令行工具共用
+
+pub mod audio;
+pub mod config;
+pub mod convert;
+pub mod dict_server;
+pub mod dictionary;
+pub mod dsl;
+pub mod export;
+pub mod goldendict;
+pub mod history;
+pub mod indexing;
+pub mod lucky;
+pub use mdict_parser as mdict;
+pub mod normalize;
+pub mod query;
+pub mod resource;
+pub mod rewrite;
+pub mod sanitize;
+pub mod search;
+pub mod stardict;
+pub mod style;
+pub mod util;
+pub mod vocabulary;
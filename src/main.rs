@@ -1,6 +1,11 @@
-use crate::config::{MDX_FILES, static_path};
-use crate::handlers::{handle_lucky, handle_query};
-use crate::indexing::indexing;
+use handlers::{
+    handle_audio, handle_goldendict, handle_lucky, handle_neighbours, handle_opensearch,
+    handle_query, handle_resource, handle_search, handle_style, handle_word,
+};
+use mdict_rs::config::{DICT_SERVER_ADDR, MDX_FILES, static_path};
+use mdict_rs::dict_server;
+use mdict_rs::dictionary::open_dictionaries;
+use mdict_rs::indexing::indexing;
 
 use axum::{
     Router,
@@ -8,16 +13,11 @@ use axum::{
 };
 use std::error::Error;
 use tower_http::{services::ServeDir, trace::TraceLayer};
-use tracing::info;
+use tracing::{error, info};
 use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
 
-mod config;
+mod api;
 mod handlers;
-mod indexing;
-mod lucky;
-mod mdict;
-mod query;
-mod util;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -26,16 +26,37 @@ async fn main() -> Result<(), Box<dyn Error>> {
         .with(EnvFilter::new("info"))
         .with(tracing_subscriber::fmt::layer())
         .init();
-    // 解析mdx到sqlite数据库
-    indexing(MDX_FILES, false).expect("indexing failed");
+    // 解析mdx到sqlite数据库，解析和写入都是阻塞的，不占用异步运行时的线程
+    tokio::task::spawn_blocking(|| indexing(MDX_FILES, false)).await??;
+    let dicts = open_dictionaries(MDX_FILES);
+
+    // DICT协议服务和web服务使用同样的词典
+    let dict_dicts = dicts.clone();
+    tokio::spawn(async move {
+        if let Err(e) = dict_server::serve(dict_dicts, DICT_SERVER_ADDR).await {
+            error!("dict server failed: {}", e);
+        }
+    });
 
     let static_dir = ServeDir::new(static_path()?);
 
     let app = Router::new()
         .route("/query", post(handle_query))
         .route("/lucky", get(handle_lucky))
+        .route("/word/{headword}", get(handle_word))
+        .route("/gd/{dict}/{word}", get(handle_goldendict))
+        .route("/opensearch.xml", get(handle_opensearch))
+        .route("/resource/{dict}/{*path}", get(handle_resource))
+        .route("/audio/{dict}/{*path}", get(handle_audio))
+        .route("/style/{dict}", get(handle_style))
+        .route("/search", get(handle_search))
+        .route("/neighbours", get(handle_neighbours))
+        .nest("/api/v1", api::routes())
+        .nest("/history", api::history::routes())
+        .nest("/vocabulary", api::vocabulary::routes())
         .fallback_service(static_dir)
-        .layer(TraceLayer::new_for_http());
+        .layer(TraceLayer::new_for_http())
+        .with_state(dicts);
 
     let port = 8181;
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8181").await.unwrap();
@@ -1,5 +0,0 @@
-pub mod mdx;
-
-mod header;
-mod keyblock;
-mod recordblock;
@@ -0,0 +1,85 @@
+use pinyin::ToPinyin;
+use zhconv::{Variant, zhconv};
+
+/// 是否包含汉字
+pub fn is_chinese(text: &str) -> bool {
+    text.chars().any(is_han)
+}
+
+fn is_han(c: char) -> bool {
+    matches!(c, '\u{4e00}'..='\u{9fff}' | '\u{3400}'..='\u{4dbf}' | '\u{f900}'..='\u{faff}' | '\u{20000}'..='\u{2ebef}')
+}
+
+/// 繁体转简体
+pub fn to_simplified(text: &str) -> String {
+    zhconv(text, Variant::ZhHans)
+}
+
+/// 简体转繁体
+pub fn to_traditional(text: &str) -> String {
+    zhconv(text, Variant::ZhHant)
+}
+
+/// 中文词头的拼音索引key，不带声调和空格，如 "中国" -> "zhongguo"
+/// 多音字取最常用读音，非汉字只保留字母和数字
+pub fn pinyin_key(headword: &str) -> Option<String> {
+    if !is_chinese(headword) {
+        return None;
+    }
+    let mut key = String::new();
+    for c in headword.chars() {
+        if let Some(py) = c.to_pinyin() {
+            key.push_str(py.plain());
+        } else if c.is_ascii_alphanumeric() {
+            key.push(c.to_ascii_lowercase());
+        }
+    }
+    Some(key.replace('ü', "v"))
+}
+
+/// 把用户输入的拼音规范化成和 pinyin_key 一致的形式
+/// "zhōngguó" "zhong1 guo2" "Zhong'guo" 都得到 "zhongguo"，不是拼音时返回None
+pub fn query_pinyin_key(input: &str) -> Option<String> {
+    let mut key = String::new();
+    for c in input.trim().chars() {
+        match c {
+            'a'..='z' => key.push(c),
+            'A'..='Z' => key.push(c.to_ascii_lowercase()),
+            'ā' | 'á' | 'ǎ' | 'à' => key.push('a'),
+            'ē' | 'é' | 'ě' | 'è' => key.push('e'),
+            'ī' | 'í' | 'ǐ' | 'ì' => key.push('i'),
+            'ō' | 'ó' | 'ǒ' | 'ò' => key.push('o'),
+            'ū' | 'ú' | 'ǔ' | 'ù' => key.push('u'),
+            'ü' | 'ǖ' | 'ǘ' | 'ǚ' | 'ǜ' => key.push('v'),
+            // 数字声调、音节分隔符
+            '1'..='5' | ' ' | '\'' | '’' | '-' => {}
+            _ => return None,
+        }
+    }
+    if key.is_empty() { None } else { Some(key) }
+}
+
+/// 一个查询词的所有写法：原词、简体、繁体，去重
+pub fn variants(word: &str) -> Vec<String> {
+    let mut words = vec![word.to_string()];
+    if is_chinese(word) {
+        for w in [to_simplified(word), to_traditional(word)] {
+            if !words.contains(&w) {
+                words.push(w);
+            }
+        }
+    }
+    words
+}
+
+/// 词头需要额外索引的key：简体、繁体写法和拼音
+pub fn headword_keys(headword: &str) -> Vec<String> {
+    let mut keys: Vec<String> = variants(headword).into_iter().skip(1).collect();
+    if let Some(py) = pinyin_key(headword)
+        && !py.is_empty()
+        && !keys.contains(&py)
+    {
+        keys.push(py);
+    }
+    keys
+}
@@ -1,22 +1,176 @@
-use rusqlite::named_params;
+use std::sync::Arc;
+
+use serde_derive::Serialize;
 use tracing::info;
 
-use crate::config::{get_db_connection, MDX_FILES};
-
-pub fn query(word: String) -> String {
-    let w = word;
-    for file in MDX_FILES {
-        let conn = get_db_connection(file).unwrap();
-        let mut stmt = conn
-            .prepare("select * from MDX_INDEX WHERE text= :word limit 1;")
-            .unwrap();
-        info!("query params={}, dict={}", &w, file);
-
-        let mut rows = stmt.query(named_params! { ":word": w }).unwrap();
-        let row = rows.next().unwrap();
-        if let Some(row) = row {
-            return row.get::<usize, String>(1).unwrap();
+use crate::dictionary::{Dictionary, Entry, find_dictionary};
+use crate::normalize::{query_pinyin_key, variants};
+use crate::rewrite::{resource_url, rewrite_links};
+use crate::sanitize::{TrustLevel, sanitize};
+use crate::style::dict_assets;
+use crate::util::escape_html;
+
+mod text;
+
+pub use text::{TextFormat, html_to_text, render_result};
+
+/// 跳转词条的前缀，如 "@@@LINK=apple"
+const LINK_PREFIX: &str = "@@@LINK=";
+/// 最多跟随的跳转次数，避免词典中的循环跳转
+pub(crate) const MAX_REDIRECTS: usize = 5;
+
+/// 一个词典中的查询结果
+#[derive(Serialize, Debug)]
+pub struct LookupResult {
+    pub dictionary: String,
+    pub headword: String,
+    pub html: String,
+    // 通过@@@LINK跳转得到结果时，记录原词头
+    pub redirected_from: Option<String>,
+}
+
+/// 在所有词典中查询，返回第一个结果的html，没有查到时为 "not found"
+pub fn query(dicts: &[Arc<dyn Dictionary>], word: String) -> anyhow::Result<String> {
+    let results = lookup_all(dicts, &word, None)?;
+    Ok(match results.first() {
+        Some(result) => render(dicts, result),
+        None => "not found".to_string(),
+    })
+}
+
+/// 页面中显示的查询结果: 用词典容器包裹释义，并引入该词典的css和js
+/// css由 /style/{dict} 加上容器选择器前缀后返回，只作用于这个词典的结果
+pub fn render(dicts: &[Arc<dyn Dictionary>], result: &LookupResult) -> String {
+    let dict = &result.dictionary;
+    let assets = find_dictionary(dicts, dict)
+        .map(|d| dict_assets(d.as_ref()))
+        .unwrap_or_default();
+    let mut html = format!(
+        r#"<div class="mdict-dict" data-dict="{}">"#,
+        escape_html(dict)
+    );
+    if assets.iter().any(|a| a.ends_with(".css")) {
+        html.push_str(&format!(
+            r#"<link rel="stylesheet" type="text/css" href="/style/{}"/>"#,
+            percent_encoding::utf8_percent_encode(dict, percent_encoding::NON_ALPHANUMERIC)
+        ));
+    }
+    html.push_str(&result.html);
+    let scripts = assets
+        .iter()
+        .filter(|a| a.ends_with(".js") && TrustLevel::of(dict).allows_scripts());
+    for js in scripts {
+        html.push_str(&format!(
+            r#"<script src="{}"></script>"#,
+            resource_url(dict, js)
+        ));
+    }
+    html.push_str("</div>");
+    html
+}
+
+/// 在所有词典(或指定词典)中查询，每个词典最多一条结果，按配置的词典顺序
+pub fn lookup_all(
+    dicts: &[Arc<dyn Dictionary>],
+    word: &str,
+    dict: Option<&str>,
+) -> anyhow::Result<Vec<LookupResult>> {
+    // 简繁体写法都直接查词头，拼音和其他写法查词典的其他key
+    let words = variants(word);
+    let pinyin = query_pinyin_key(word);
+    let mut results = vec![];
+    for d in dicts {
+        let name = &d.info().name;
+        if dict.is_some_and(|n| n != name) {
+            continue;
+        }
+        info!("query params={}, dict={}", word, d.info().file);
+
+        if let Some(entry) = lookup(d.as_ref(), &words, pinyin.as_deref())? {
+            let (entry, redirected_from) = follow_links(d.as_ref(), entry)?;
+            // 释义中的词条链接、发音和资源路径改为本站的url
+            // 再按词典的信任级别清理html，去掉script和事件属性
+            let html = rewrite_links(entry.definition.trim_end_matches('\0'), name)?;
+            let html = sanitize(&html, name);
+            results.push(LookupResult {
+                dictionary: name.clone(),
+                headword: entry.headword,
+                html,
+                redirected_from,
+            });
+        }
+    }
+    Ok(results)
+}
+
+/// 在一个词典中查询，先精确匹配词头，再匹配简繁体和拼音key
+fn lookup(
+    dict: &dyn Dictionary,
+    words: &[String],
+    pinyin: Option<&str>,
+) -> anyhow::Result<Option<Entry>> {
+    for word in words {
+        if let Some(entry) = dict.lookup(word)? {
+            return Ok(Some(entry));
+        }
+    }
+    for key in words.iter().map(String::as_str).chain(pinyin) {
+        if let Some(entry) = dict.lookup_key(key)? {
+            return Ok(Some(entry));
+        }
+    }
+    Ok(None)
+}
+
+/// 跟随 @@@LINK= 跳转到目标词条，返回目标词条和原词头
+fn follow_links(dict: &dyn Dictionary, entry: Entry) -> anyhow::Result<(Entry, Option<String>)> {
+    let headword = entry.headword.clone();
+    let mut current = entry;
+    for _ in 0..MAX_REDIRECTS {
+        let Some(target) = link_target(&current.definition) else {
+            break;
+        };
+        let Some(target) = dict.lookup(target)? else {
+            break;
+        };
+        current = target;
+    }
+    let redirected_from = (current.headword != headword).then_some(headword);
+    Ok((current, redirected_from))
+}
+
+/// "@@@LINK=apple\r\n" 中的 "apple"
+pub fn link_target(def: &str) -> Option<&str> {
+    def.strip_prefix(LINK_PREFIX)
+        .map(|t| t.trim_end_matches(['\r', '\n', '\0']).trim())
+}
+
+/// 一个词头在词典顺序中的前后词条
+#[derive(Serialize, Debug)]
+pub struct Neighbours {
+    pub dictionary: String,
+    pub headword: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+/// 按词典顺序查找词头前后各n个词条，dict为空时使用第一个包含该词的词典
+pub fn neighbours(
+    dicts: &[Arc<dyn Dictionary>],
+    word: &str,
+    dict: Option<&str>,
+    n: usize,
+) -> anyhow::Result<Option<Neighbours>> {
+    let words = variants(word);
+    for d in dicts {
+        if dict.is_some_and(|name| name != d.info().name) {
+            continue;
+        }
+        for w in &words {
+            if let Some(found) = d.neighbours(w, n)? {
+                return Ok(Some(found));
+            }
         }
     }
-    "not found".to_string()
+    Ok(None)
 }
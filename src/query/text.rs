@@ -0,0 +1,231 @@
+use ego_tree::NodeRef;
+use scraper::{Html, Node};
+
+use crate::query::LookupResult;
+
+/// 释义的非html输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextFormat {
+    Text,
+    Markdown,
+}
+
+impl TextFormat {
+    /// "text" "txt" "plain" -> Text, "markdown" "md" -> Markdown
+    pub fn parse(name: &str) -> Option<TextFormat> {
+        match name.trim().to_lowercase().as_str() {
+            "text" | "txt" | "plain" => Some(TextFormat::Text),
+            "markdown" | "md" => Some(TextFormat::Markdown),
+            _ => None,
+        }
+    }
+}
+
+/// 例句常用的class，单独成行显示
+const EXAMPLE_CLASSES: &[&str] = &["x", "eg", "ex", "example", "EXAMPLE", "x-g"];
+/// 词性常用的class，markdown中显示为斜体
+const POS_CLASSES: &[&str] = &["pos", "POS", "ps", "cixing"];
+
+/// 一个查询结果渲染为文本: 词头、词典名，以及保留编号义项和例句的释义
+pub fn render_result(result: &LookupResult, format: TextFormat) -> String {
+    let body = html_to_text(&result.html, format);
+    match format {
+        TextFormat::Text => format!("{}  [{}]\n\n{}\n", result.headword, result.dictionary, body),
+        TextFormat::Markdown => {
+            format!("# {}\n\n_{}_\n\n{}\n", result.headword, result.dictionary, body)
+        }
+    }
+}
+
+/// 释义html转换为可读的纯文本或markdown
+pub fn html_to_text(html: &str, format: TextFormat) -> String {
+    let fragment = Html::parse_fragment(html);
+    let mut writer = Writer {
+        out: String::new(),
+        format,
+        lists: vec![],
+        line_start: true,
+        pending_space: false,
+    };
+    writer.children(*fragment.root_element());
+    writer.finish()
+}
+
+struct Writer {
+    out: String,
+    format: TextFormat,
+    // 列表嵌套，ol保存下一个编号，ul为None
+    lists: Vec<Option<usize>>,
+    line_start: bool,
+    pending_space: bool,
+}
+
+impl Writer {
+    fn children(&mut self, node: NodeRef<Node>) {
+        for child in node.children() {
+            self.node(child);
+        }
+    }
+
+    fn node(&mut self, node: NodeRef<Node>) {
+        match node.value() {
+            Node::Text(text) => self.text(text),
+            Node::Element(el) => {
+                let name = el.name();
+                let has_class = |classes: &[&str]| el.classes().any(|c| classes.contains(&c));
+                match name {
+                    "script" | "style" | "head" | "link" | "meta" | "title" | "template" => {}
+                    "br" => self.newline(),
+                    "img" => {
+                        if let Some(alt) = el.attr("alt") {
+                            self.text(alt);
+                        }
+                    }
+                    "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                        self.blank_line();
+                        if self.format == TextFormat::Markdown {
+                            let level = name[1..].parse().unwrap_or(1) + 1;
+                            self.raw(&format!("{} ", "#".repeat(level)));
+                        }
+                        self.children(node);
+                        self.blank_line();
+                    }
+                    "ol" | "ul" => {
+                        self.newline();
+                        self.lists.push((name == "ol").then_some(1));
+                        self.children(node);
+                        self.lists.pop();
+                        self.newline();
+                    }
+                    "li" => {
+                        self.newline();
+                        self.indent();
+                        let marker = match self.lists.last_mut() {
+                            Some(Some(n)) => {
+                                *n += 1;
+                                format!("{}. ", *n - 1)
+                            }
+                            _ if self.format == TextFormat::Markdown => "- ".to_string(),
+                            _ => "• ".to_string(),
+                        };
+                        self.raw(&marker);
+                        self.children(node);
+                        self.newline();
+                    }
+                    _ if has_class(EXAMPLE_CLASSES) => {
+                        self.newline();
+                        self.indent();
+                        self.raw(match self.format {
+                            TextFormat::Markdown => "  - ",
+                            TextFormat::Text => "  » ",
+                        });
+                        self.wrapped(node, "*");
+                        self.newline();
+                    }
+                    _ if has_class(POS_CLASSES) => self.wrapped(node, "*"),
+                    "b" | "strong" => self.wrapped(node, "**"),
+                    "i" | "em" => self.wrapped(node, "*"),
+                    "p" | "div" | "section" | "article" | "blockquote" | "tr" | "table" | "dl"
+                    | "dt" | "dd" | "header" | "footer" | "center" | "hr" => {
+                        self.newline();
+                        self.children(node);
+                        self.newline();
+                    }
+                    "td" | "th" => {
+                        self.pending_space = true;
+                        self.children(node);
+                        self.pending_space = true;
+                    }
+                    _ => self.children(node),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// markdown中用标记包裹内容，如 **bold**，纯文本只输出内容
+    fn wrapped(&mut self, node: NodeRef<Node>, mark: &str) {
+        if self.format == TextFormat::Text {
+            return self.children(node);
+        }
+        let mut inner = Writer {
+            out: String::new(),
+            format: self.format,
+            lists: vec![],
+            line_start: true,
+            pending_space: false,
+        };
+        inner.children(node);
+        let inner = inner.out.trim().to_string();
+        if !inner.is_empty() {
+            self.raw(&format!("{mark}{inner}{mark}"));
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        for c in text.chars() {
+            if c.is_whitespace() {
+                self.pending_space = !self.line_start;
+                continue;
+            }
+            if self.pending_space {
+                self.out.push(' ');
+                self.pending_space = false;
+            }
+            if self.format == TextFormat::Markdown && matches!(c, '*' | '_' | '`' | '[' | ']') {
+                self.out.push('\\');
+            }
+            self.out.push(c);
+            self.line_start = false;
+        }
+    }
+
+    /// 不做转义直接输出
+    fn raw(&mut self, text: &str) {
+        if self.pending_space {
+            self.out.push(' ');
+            self.pending_space = false;
+        }
+        self.out.push_str(text);
+        self.line_start = false;
+    }
+
+    fn indent(&mut self) {
+        let depth = self.lists.len().saturating_sub(1);
+        self.out.push_str(&"   ".repeat(depth));
+    }
+
+    fn newline(&mut self) {
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+        self.line_start = true;
+        self.pending_space = false;
+    }
+
+    fn blank_line(&mut self) {
+        self.newline();
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn finish(self) -> String {
+        let mut out = String::new();
+        let mut blank = 0;
+        for line in self.out.lines().map(str::trim_end) {
+            if line.trim().is_empty() {
+                blank += 1;
+                continue;
+            }
+            if !out.is_empty() {
+                out.push_str(if blank > 0 { "\n\n" } else { "\n" });
+            }
+            out.push_str(line);
+            blank = 0;
+        }
+        out
+    }
+}
@@ -0,0 +1,40 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::static_path;
+use crate::dictionary::Dictionary;
+use crate::util::{normalize_resource_path, safe_relative_path};
+
+/// 查找词典的资源文件，先查找词典自己的资源，再查找static目录
+pub fn find_resource(dict: &dyn Dictionary, path: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    match dict.resource(path)? {
+        Some(data) => Ok(Some(data)),
+        None => read_from_dirs(&[static_path()?], path),
+    }
+}
+
+/// 词典文件所在目录和其中res目录下的资源文件，stardict的资源在res目录中
+pub fn dir_resource(file: &str, path: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    let Some(dir) = Path::new(file).parent() else {
+        return Ok(None);
+    };
+    read_from_dirs(&[dir.to_path_buf(), dir.join("res")], path)
+}
+
+/// 目录中的文件区分大小写，先按原始路径查找，再按统一为小写的路径查找
+fn read_from_dirs(dirs: &[PathBuf], path: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    let original = path.replace('\\', "/");
+    let normalized = normalize_resource_path(path);
+    for dir in dirs {
+        for relative in [original.trim_start_matches('/'), &normalized]
+            .into_iter()
+            .filter_map(safe_relative_path)
+        {
+            let candidate = dir.join(relative);
+            if candidate.is_file() {
+                return Ok(Some(fs::read(candidate)?));
+            }
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,113 @@
+use lol_html::{RewriteStrSettings, element, rewrite_str};
+use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
+
+use crate::util::normalize_resource_path;
+
+/// url路径中的一段需要转义的字符
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'?')
+    .add(b'<')
+    .add(b'>')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// 改写释义html中的链接:
+/// entry://word bword://word 改为查询链接 /word/{word}
+/// sound://uk/a.spx 改为音频链接 /audio/{dict}/{path}，并标记为可播放的发音
+/// 图片、css、js等相对路径改为当前词典的资源链接 /resource/{dict}/{path}
+pub fn rewrite_links(html: &str, dict: &str) -> anyhow::Result<String> {
+    let rewritten = rewrite_str(
+        html,
+        RewriteStrSettings::new()
+            .append_element_content_handler(element!("a[href]", |el| {
+                let href = el.get_attribute("href").unwrap_or_default();
+                if let Some(word) = cross_reference(&href) {
+                    el.set_attribute("href", &word)?;
+                } else if let Some(path) = href.strip_prefix("sound://") {
+                    el.set_attribute("href", &audio_url(dict, path))?;
+                    el.set_attribute("data-sound", "true")?;
+                }
+                Ok(())
+            }))
+            .append_element_content_handler(element!(
+                "img[src], script[src], source[src], audio[src], embed[src], input[src]",
+                |el| {
+                    let src = el.get_attribute("src").unwrap_or_default();
+                    if let Some(path) = local_path(&src) {
+                        el.set_attribute("src", &resource_url(dict, path))?;
+                    }
+                    Ok(())
+                }
+            ))
+            .append_element_content_handler(element!("link[href]", |el| {
+                let href = el.get_attribute("href").unwrap_or_default();
+                if let Some(path) = local_path(&href) {
+                    el.set_attribute("href", &resource_url(dict, path))?;
+                }
+                Ok(())
+            })),
+    )?;
+    Ok(rewritten)
+}
+
+/// 词典资源的url
+pub fn resource_url(dict: &str, path: &str) -> String {
+    format!("/resource/{}", dict_path(dict, path))
+}
+
+/// 发音的url，speex会被转码为浏览器可以播放的格式
+pub fn audio_url(dict: &str, path: &str) -> String {
+    format!("/audio/{}", dict_path(dict, path))
+}
+
+/// "{dict}/{path}"，各段分别转义
+fn dict_path(dict: &str, path: &str) -> String {
+    let path = normalize_resource_path(path)
+        .split('/')
+        .map(|seg| utf8_percent_encode(seg, PATH_SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/");
+    format!("{}/{}", utf8_percent_encode(dict, PATH_SEGMENT), path)
+}
+
+/// entry://apple -> /word/apple，entry://#sec 是页内锚点 -> #sec
+fn cross_reference(href: &str) -> Option<String> {
+    let target = href
+        .strip_prefix("entry://")
+        .or_else(|| href.strip_prefix("bword://"))?;
+    if target.starts_with('#') {
+        return Some(target.to_string());
+    }
+    let (word, fragment) = match target.split_once('#') {
+        Some((word, fragment)) => (word, format!("#{fragment}")),
+        None => (target, String::new()),
+    };
+    Some(format!(
+        "/word/{}{}",
+        utf8_percent_encode(word.trim(), PATH_SEGMENT),
+        fragment
+    ))
+}
+
+/// 指向词典自带资源的路径: 相对路径、file://、sound:// 以及以单个/开头的路径
+pub(crate) fn local_path(url: &str) -> Option<&str> {
+    if let Some(path) = url
+        .strip_prefix("file://")
+        .or_else(|| url.strip_prefix("sound://"))
+    {
+        return Some(path);
+    }
+    let is_remote = url.is_empty()
+        || url.starts_with("//")
+        || url.starts_with('#')
+        || url.contains("://")
+        || url.starts_with("data:")
+        || url.starts_with("javascript:");
+    if is_remote { None } else { Some(url) }
+}
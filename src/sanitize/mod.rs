@@ -0,0 +1,54 @@
+use std::sync::LazyLock;
+
+use ammonia::Builder;
+
+use crate::config::{
+    DICT_TRUST, SANITIZE_EXTRA_TAGS, SANITIZE_GENERIC_ATTRIBUTES, SANITIZE_TAG_ATTRIBUTES,
+};
+
+/// 词典的信任级别，决定释义html是否清理以及是否加载词典自带的js
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrustLevel {
+    /// 清理释义html，不加载词典的js，未配置的词典默认使用
+    Untrusted,
+    /// 清理释义html，但加载配置的词典js，如 hycd_3rd.js
+    BundledScripts,
+    /// 不清理，释义中的script和事件属性都会执行
+    #[allow(unused)]
+    Trusted,
+}
+
+impl TrustLevel {
+    pub fn of(dict: &str) -> TrustLevel {
+        DICT_TRUST
+            .iter()
+            .find(|(name, _)| *name == dict)
+            .map_or(TrustLevel::Untrusted, |(_, level)| *level)
+    }
+
+    /// 是否加载词典配置的js
+    pub fn allows_scripts(self) -> bool {
+        self != TrustLevel::Untrusted
+    }
+}
+
+/// 在ammonia默认白名单的基础上，加上配置中的标签和属性，data-*属性也保留(如发音链接的data-sound)
+static SANITIZER: LazyLock<Builder<'static>> = LazyLock::new(|| {
+    let mut builder = Builder::default();
+    builder
+        .add_tags(SANITIZE_EXTRA_TAGS)
+        .add_generic_attributes(SANITIZE_GENERIC_ATTRIBUTES)
+        .add_generic_attribute_prefixes(["data-"]);
+    for (tag, attributes) in SANITIZE_TAG_ATTRIBUTES {
+        builder.add_tag_attributes(tag, attributes.iter());
+    }
+    builder
+});
+
+/// 按词典的信任级别清理释义html，去掉script、事件属性、javascript:链接等
+pub fn sanitize(html: &str, dict: &str) -> String {
+    match TrustLevel::of(dict) {
+        TrustLevel::Trusted => html.to_string(),
+        _ => SANITIZER.clean(html).to_string(),
+    }
+}
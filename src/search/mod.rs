@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use serde_derive::Serialize;
+
+use crate::dictionary::Dictionary;
+use crate::util::escape_html;
+
+/// 全文检索的一条结果
+#[derive(Serialize, Debug)]
+pub struct SearchHit {
+    pub dictionary: String,
+    pub headword: String,
+    // 命中片段，关键词用<mark>包裹
+    pub snippet: String,
+    // fts5 bm25 得分，越小越相关
+    pub rank: f64,
+}
+
+/// 在释义中全文检索短语，dict为空时检索所有词典，结果按相关度排序
+pub fn search(
+    dicts: &[Arc<dyn Dictionary>],
+    phrase: &str,
+    dict: Option<&str>,
+    limit: usize,
+) -> anyhow::Result<Vec<SearchHit>> {
+    let phrase = phrase.trim();
+    if phrase.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut hits = vec![];
+    for d in dicts {
+        if dict.is_some_and(|name| name != d.info().name) {
+            continue;
+        }
+        hits.extend(d.search(phrase, limit)?);
+    }
+
+    hits.sort_by(|a, b| a.rank.total_cmp(&b.rank));
+    hits.truncate(limit);
+    Ok(hits)
+}
+
+/// 转义片段中的html字符，再把fts5标记的命中位置替换为<mark>
+pub(crate) fn highlight(snippet: &str) -> String {
+    escape_html(snippet)
+        .replace('\u{2}', "<mark>")
+        .replace('\u{3}', "</mark>")
+}
@@ -0,0 +1,298 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{Context, anyhow, bail};
+use flate2::read::GzDecoder;
+use flate2::{Compress, Compression, Crc, Decompress, FlushCompress, FlushDecompress, Status};
+
+/// gzip header中的标志位
+const FHCRC: u8 = 2;
+const FEXTRA: u8 = 4;
+const FNAME: u8 = 8;
+const FCOMMENT: u8 = 16;
+/// dictzip工具默认的chunk大小，压缩后的大小不会超过u16
+const CHUNK_LEN: usize = 58315;
+
+/// .dict 文件的内容，.dict.dz 是dictzip格式时按chunk随机读取，普通gzip则整个解压
+pub(crate) enum DictData {
+    Plain(Vec<u8>),
+    Dictzip(Dictzip),
+}
+
+impl DictData {
+    pub(crate) fn open(path: &Path) -> anyhow::Result<DictData> {
+        let data = fs::read(path).with_context(|| format!("read {} failed", path.display()))?;
+        if path.extension().is_none_or(|e| e != "dz") {
+            return Ok(DictData::Plain(data));
+        }
+        match Dictzip::new(data)? {
+            Ok(dictzip) => Ok(DictData::Dictzip(dictzip)),
+            // 没有RA扩展字段的普通gzip
+            Err(data) => {
+                let mut plain = vec![];
+                GzDecoder::new(&data[..])
+                    .read_to_end(&mut plain)
+                    .with_context(|| format!("gunzip {} failed", path.display()))?;
+                Ok(DictData::Plain(plain))
+            }
+        }
+    }
+
+    /// 读取解压后[offset, offset+size)的数据
+    pub(crate) fn read(&self, offset: usize, size: usize) -> anyhow::Result<Vec<u8>> {
+        match self {
+            DictData::Plain(data) => offset
+                .checked_add(size)
+                .and_then(|end| data.get(offset..end))
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| anyhow!("entry at {offset}+{size} is beyond the dict file")),
+            DictData::Dictzip(dictzip) => dictzip.read(offset, size),
+        }
+    }
+}
+
+/// dictzip: gzip的FEXTRA中有 "RA" 字段，记录每个chunk压缩后的大小
+/// 每个chunk解压后为chunk_len字节，可以单独用raw deflate解压
+pub(crate) struct Dictzip {
+    data: Vec<u8>,
+    chunk_len: usize,
+    // 每个chunk在data中的起始位置，最后一项是结束位置
+    chunk_offsets: Vec<usize>,
+    // 最近解压的chunk，顺序读取时同一个chunk中有很多词条
+    cache: Mutex<Option<(usize, Vec<u8>)>>,
+}
+
+impl Dictzip {
+    /// 不是dictzip时原样返回数据
+    fn new(data: Vec<u8>) -> anyhow::Result<Result<Dictzip, Vec<u8>>> {
+        if data.len() < 10 || data[0..3] != [0x1f, 0x8b, 8] {
+            bail!("not a gzip file");
+        }
+        let flags = data[3];
+        let mut pos = 10;
+        let mut chunks = None;
+        if flags & FEXTRA != 0 {
+            let xlen = u16_le(&data, pos)? as usize;
+            let extra = data
+                .get(pos + 2..pos + 2 + xlen)
+                .ok_or_else(|| anyhow!("truncated gzip extra field"))?;
+            chunks = ra_field(extra)?;
+            pos += 2 + xlen;
+        }
+        for flag in [FNAME, FCOMMENT] {
+            if flags & flag != 0 {
+                let end = data[pos..]
+                    .iter()
+                    .position(|&b| b == 0)
+                    .ok_or_else(|| anyhow!("truncated gzip header"))?;
+                pos += end + 1;
+            }
+        }
+        if flags & FHCRC != 0 {
+            pos += 2;
+        }
+        let Some((chunk_len, sizes)) = chunks else {
+            return Ok(Err(data));
+        };
+
+        let mut chunk_offsets = vec![pos];
+        for size in sizes {
+            chunk_offsets.push(chunk_offsets.last().unwrap() + size);
+        }
+        if *chunk_offsets.last().unwrap() > data.len() {
+            bail!("dictzip chunks are beyond the end of file");
+        }
+        Ok(Ok(Dictzip {
+            data,
+            chunk_len,
+            chunk_offsets,
+            cache: Mutex::new(None),
+        }))
+    }
+
+    fn read(&self, offset: usize, size: usize) -> anyhow::Result<Vec<u8>> {
+        // 所有chunk解压后最多 chunk_len*chunk数 字节，超出的词条不用解压就可以报错
+        let chunks = self.chunk_offsets.len() - 1;
+        let Some(entry_end) = offset
+            .checked_add(size)
+            .filter(|&end| end <= self.chunk_len.saturating_mul(chunks))
+        else {
+            bail!("entry at {offset}+{size} is beyond the dict file");
+        };
+        let mut out = Vec::with_capacity(size);
+        let first = offset / self.chunk_len;
+        let last = entry_end.saturating_sub(1) / self.chunk_len;
+        for i in first..=last {
+            let chunk = self.chunk(i)?;
+            let start = if i == first {
+                offset % self.chunk_len
+            } else {
+                0
+            };
+            let end = (entry_end - i * self.chunk_len).min(chunk.len());
+            if start > end {
+                bail!("entry at {offset}+{size} is beyond the dict file");
+            }
+            out.extend_from_slice(&chunk[start..end]);
+        }
+        if out.len() != size {
+            bail!("entry at {offset}+{size} is beyond the dict file");
+        }
+        Ok(out)
+    }
+
+    /// 解压第i个chunk
+    fn chunk(&self, i: usize) -> anyhow::Result<Vec<u8>> {
+        let mut cache = self.cache.lock().unwrap();
+        if let Some((n, data)) = cache.as_ref()
+            && *n == i
+        {
+            return Ok(data.clone());
+        }
+        let (Some(&start), Some(&end)) = (self.chunk_offsets.get(i), self.chunk_offsets.get(i + 1))
+        else {
+            bail!("dictzip chunk {i} does not exist");
+        };
+        let mut out = Vec::with_capacity(self.chunk_len);
+        Decompress::new(false)
+            .decompress_vec(&self.data[start..end], &mut out, FlushDecompress::Sync)
+            .with_context(|| format!("inflate dictzip chunk {i} failed"))?;
+        *cache = Some((i, out.clone()));
+        Ok(out)
+    }
+}
+
+/// FEXTRA中的 "RA" 字段: ver(u16) chlen(u16) chcnt(u16) 以及chcnt个chunk压缩后的大小
+fn ra_field(mut extra: &[u8]) -> anyhow::Result<Option<(usize, Vec<usize>)>> {
+    while extra.len() >= 4 {
+        let len = u16_le(extra, 2)? as usize;
+        let field = extra
+            .get(4..4 + len)
+            .ok_or_else(|| anyhow!("truncated gzip extra field"))?;
+        if extra[0..2] == *b"RA" {
+            let chunk_len = u16_le(field, 2)? as usize;
+            if chunk_len == 0 {
+                bail!("dictzip chunk length is 0");
+            }
+            let count = u16_le(field, 4)? as usize;
+            let sizes = (0..count)
+                .map(|i| u16_le(field, 6 + i * 2).map(usize::from))
+                .collect::<anyhow::Result<_>>()?;
+            return Ok(Some((chunk_len, sizes)));
+        }
+        extra = &extra[4 + len..];
+    }
+    Ok(None)
+}
+
+fn u16_le(data: &[u8], pos: usize) -> anyhow::Result<u16> {
+    data.get(pos..pos + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| anyhow!("truncated dictzip header"))
+}
+
+/// 写入dictzip格式的 .dict.dz，每个chunk用full flush结束，可以单独解压
+pub(crate) fn write_dictzip(data: &[u8], out: &mut impl Write) -> anyhow::Result<()> {
+    let mut compress = Compress::new(Compression::best(), false);
+    let mut chunks = vec![];
+    let mut sizes = vec![];
+    for (i, chunk) in data.chunks(CHUNK_LEN).enumerate() {
+        let last = (i + 1) * CHUNK_LEN >= data.len();
+        let flush = if last {
+            FlushCompress::Finish
+        } else {
+            FlushCompress::Full
+        };
+        let before = chunks.len();
+        deflate(&mut compress, chunk, &mut chunks, flush)?;
+        sizes.push(u16::try_from(chunks.len() - before)?);
+    }
+    if data.is_empty() {
+        deflate(&mut compress, &[], &mut chunks, FlushCompress::Finish)?;
+        sizes.push(u16::try_from(chunks.len())?);
+    }
+
+    // RA: ver=1 chlen chcnt 以及每个chunk的大小
+    let mut ra = vec![];
+    for n in [1, CHUNK_LEN as u16, u16::try_from(sizes.len())?]
+        .into_iter()
+        .chain(sizes)
+    {
+        ra.extend_from_slice(&n.to_le_bytes());
+    }
+    let mut extra = b"RA".to_vec();
+    extra.extend_from_slice(&u16::try_from(ra.len())?.to_le_bytes());
+    extra.extend_from_slice(&ra);
+
+    // gzip header: magic, deflate, FEXTRA, mtime=0, xfl=2(最高压缩), os=3(unix)
+    out.write_all(&[0x1f, 0x8b, 8, FEXTRA, 0, 0, 0, 0, 2, 3])?;
+    out.write_all(&u16::try_from(extra.len())?.to_le_bytes())?;
+    out.write_all(&extra)?;
+    out.write_all(&chunks)?;
+    let mut crc = Crc::new();
+    crc.update(data);
+    out.write_all(&crc.sum().to_le_bytes())?;
+    out.write_all(&(data.len() as u32).to_le_bytes())?;
+    Ok(())
+}
+
+/// 压缩input并完成flush，输出空间不够时继续调用
+fn deflate(
+    compress: &mut Compress,
+    input: &[u8],
+    out: &mut Vec<u8>,
+    flush: FlushCompress,
+) -> anyhow::Result<()> {
+    let start = compress.total_in();
+    loop {
+        let consumed = (compress.total_in() - start) as usize;
+        out.reserve(CHUNK_LEN);
+        let status = compress.compress_vec(&input[consumed..], out, flush)?;
+        let flushed =
+            compress.total_in() - start == input.len() as u64 && out.len() < out.capacity();
+        match (flush, status) {
+            (_, Status::StreamEnd) => return Ok(()),
+            (FlushCompress::Finish, _) => {}
+            _ if flushed => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dictzip(data: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        write_dictzip(data, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn read_chunks() {
+        let data: Vec<u8> = (0..CHUNK_LEN * 2 + 100).map(|i| i as u8).collect();
+        let dictzip = Dictzip::new(dictzip(&data)).unwrap().ok().unwrap();
+        assert_eq!(dictzip.read(10, 5).unwrap(), &data[10..15]);
+        // 跨越chunk边界
+        let offset = CHUNK_LEN - 3;
+        assert_eq!(
+            dictzip.read(offset, 10).unwrap(),
+            &data[offset..offset + 10]
+        );
+        assert!(dictzip.read(data.len() - 1, 2).is_err());
+        assert!(dictzip.read(usize::MAX, 2).is_err());
+        assert!(DictData::Plain(data).read(usize::MAX, 2).is_err());
+    }
+
+    #[test]
+    fn zero_chunk_len() {
+        let mut data = dictzip(b"apple");
+        // header 10字节, xlen, "RA", len, ver 之后是 chlen
+        assert_eq!(&data[12..14], b"RA");
+        data[18..20].copy_from_slice(&0_u16.to_le_bytes());
+        assert!(Dictzip::new(data).is_err());
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use anyhow::{Context, anyhow, bail};
+use flate2::read::GzDecoder;
+use regex::{Captures, Regex};
+
+use crate::util::escape_html;
+use dictzip::DictData;
+pub(crate) use dictzip::write_dictzip;
+
+mod dictzip;
+
+/// .ifo 文件的第一行
+pub(crate) const IFO_MAGIC: &str = "StarDict's dict ifo file";
+
+/// .idx 中的一个词条，offset和size是释义在 .dict 解压后的位置
+#[derive(Debug)]
+struct IdxEntry {
+    word: String,
+    offset: usize,
+    size: usize,
+}
+
+/// StarDict词典: .ifo 词典信息, .idx(.gz) 词头索引, .dict(.dz) 释义, 可选的 .syn 同义词
+pub struct StarDict {
+    // .ifo 中的 key=value，如 bookname wordcount sametypesequence
+    pub info: HashMap<String, String>,
+    entries: Vec<IdxEntry>,
+    // (同义词, entries中的序号)
+    synonyms: Vec<(String, usize)>,
+    dict: DictData,
+}
+
+impl StarDict {
+    /// 打开 .ifo 文件，同目录下同名的 .idx .dict .syn 一起读取
+    pub fn open(ifo: &Path) -> anyhow::Result<StarDict> {
+        let text =
+            fs::read_to_string(ifo).with_context(|| format!("read {} failed", ifo.display()))?;
+        let mut lines = text.lines();
+        if lines
+            .next()
+            .map(|l| l.trim_start_matches('\u{feff}').trim())
+            != Some(IFO_MAGIC)
+        {
+            bail!("{} is not a stardict ifo file", ifo.display());
+        }
+        let info: HashMap<String, String> = lines
+            .filter_map(|l| l.split_once('='))
+            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+            .collect();
+        let offset_bits = info.get("idxoffsetbits").map_or("32", String::as_str);
+
+        let idx = read_maybe_gz(&sibling(ifo, &["idx", "idx.gz"])?)?;
+        let entries = parse_idx(&idx, offset_bits == "64")?;
+        let synonyms = match sibling(ifo, &["syn", "syn.dz"]) {
+            Ok(syn) => parse_syn(&read_maybe_gz(&syn)?, entries.len())?,
+            Err(_) => vec![],
+        };
+        let dict = DictData::open(&sibling(ifo, &["dict", "dict.dz"])?)?;
+        Ok(StarDict {
+            info,
+            entries,
+            synonyms,
+            dict,
+        })
+    }
+
+    /// 按idx中的顺序返回(词头, html释义)，相邻的同名词条合并为一条
+    pub fn items(&self) -> impl Iterator<Item = anyhow::Result<(String, String)>> + '_ {
+        let mut i = 0;
+        std::iter::from_fn(move || {
+            let word = &self.entries.get(i)?.word;
+            let mut html = String::new();
+            while let Some(entry) = self.entries.get(i).filter(|e| e.word == *word) {
+                let definition = match self.definition(entry) {
+                    Ok(definition) => definition,
+                    Err(e) => return Some(Err(e)),
+                };
+                if !html.is_empty() {
+                    html.push_str("<hr/>");
+                }
+                html.push_str(&definition);
+                i += 1;
+            }
+            Some(Ok((word.clone(), html)))
+        })
+    }
+
+    /// 同义词和对应的词头
+    pub fn synonyms(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.synonyms
+            .iter()
+            .map(|(syn, i)| (syn.as_str(), self.entries[*i].word.as_str()))
+    }
+
+    fn definition(&self, entry: &IdxEntry) -> anyhow::Result<String> {
+        let data = self
+            .dict
+            .read(entry.offset, entry.size)
+            .with_context(|| format!("read definition of {} failed", entry.word))?;
+        let sequence = self.info.get("sametypesequence").map(String::as_str);
+        Ok(fields(&data, sequence)
+            .into_iter()
+            .map(|(t, field)| field_html(t, field))
+            .collect())
+    }
+}
+
+/// ifo同目录同名、扩展名为exts之一的文件
+fn sibling(ifo: &Path, exts: &[&str]) -> anyhow::Result<PathBuf> {
+    exts.iter()
+        .map(|ext| ifo.with_extension(ext))
+        .find(|p| p.exists())
+        .ok_or_else(|| anyhow!("{} has no .{} file", ifo.display(), exts[0]))
+}
+
+/// .gz .dz 结尾的文件整个解压
+fn read_maybe_gz(path: &Path) -> anyhow::Result<Vec<u8>> {
+    let data = fs::read(path).with_context(|| format!("read {} failed", path.display()))?;
+    if path.extension().is_none_or(|e| e != "gz" && e != "dz") {
+        return Ok(data);
+    }
+    let mut plain = vec![];
+    GzDecoder::new(&data[..])
+        .read_to_end(&mut plain)
+        .with_context(|| format!("gunzip {} failed", path.display()))?;
+    Ok(plain)
+}
+
+/// .idx: 以\0结尾的utf-8词头 + be_u32(64位时be_u64)偏移 + be_u32大小
+fn parse_idx(mut data: &[u8], offset64: bool) -> anyhow::Result<Vec<IdxEntry>> {
+    let offset_len = if offset64 { 8 } else { 4 };
+    let mut entries = vec![];
+    while !data.is_empty() {
+        let (word, rest) = c_string(data)?;
+        let nums = rest
+            .get(..offset_len + 4)
+            .ok_or_else(|| anyhow!("truncated idx entry {word}"))?;
+        let offset = if offset64 {
+            u64::from_be_bytes(nums[..8].try_into().unwrap()) as usize
+        } else {
+            u32::from_be_bytes(nums[..4].try_into().unwrap()) as usize
+        };
+        let size = u32::from_be_bytes(nums[offset_len..].try_into().unwrap()) as usize;
+        entries.push(IdxEntry { word, offset, size });
+        data = &rest[offset_len + 4..];
+    }
+    Ok(entries)
+}
+
+/// .syn: 以\0结尾的utf-8同义词 + be_u32 idx中的序号
+fn parse_syn(mut data: &[u8], entries: usize) -> anyhow::Result<Vec<(String, usize)>> {
+    let mut synonyms = vec![];
+    while !data.is_empty() {
+        let (word, rest) = c_string(data)?;
+        let index = rest
+            .get(..4)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()) as usize)
+            .ok_or_else(|| anyhow!("truncated syn entry {word}"))?;
+        if index < entries {
+            synonyms.push((word, index));
+        }
+        data = &rest[4..];
+    }
+    Ok(synonyms)
+}
+
+fn c_string(data: &[u8]) -> anyhow::Result<(String, &[u8])> {
+    let end = data
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| anyhow!("missing \\0 after word"))?;
+    Ok((
+        String::from_utf8_lossy(&data[..end]).to_string(),
+        &data[end + 1..],
+    ))
+}
+
+/// 一个释义由多个字段组成，小写类型以\0结尾，大写类型以be_u32大小开头
+/// 有sametypesequence时字段不带类型字符，并且最后一个字段没有\0或大小
+fn fields<'a>(mut data: &'a [u8], sequence: Option<&str>) -> Vec<(u8, &'a [u8])> {
+    let mut fields = vec![];
+    match sequence {
+        Some(sequence) => {
+            let types = sequence.as_bytes();
+            for (i, &t) in types.iter().enumerate() {
+                if i == types.len() - 1 {
+                    fields.push((t, data));
+                    break;
+                }
+                let Some((field, rest)) = field(data, t) else {
+                    break;
+                };
+                fields.push((t, field));
+                data = rest;
+            }
+        }
+        None => {
+            while let Some((&t, rest)) = data.split_first() {
+                let Some((field, rest)) = field(rest, t) else {
+                    break;
+                };
+                fields.push((t, field));
+                data = rest;
+            }
+        }
+    }
+    fields
+}
+
+fn field(data: &[u8], t: u8) -> Option<(&[u8], &[u8])> {
+    if t.is_ascii_lowercase() {
+        let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+        Some((&data[..end], data.get(end + 1..).unwrap_or_default()))
+    } else {
+        let size = u32::from_be_bytes(data.get(..4)?.try_into().unwrap()) as usize;
+        Some((data.get(4..4 + size)?, &data[4 + size..]))
+    }
+}
+
+/// 按字段类型转换为html: h html, g pango标记, x xdxf, t 音标, r 资源列表，其余作为纯文本
+/// W P 等二进制字段没有对应的显示，忽略
+fn field_html(t: u8, data: &[u8]) -> String {
+    let text = String::from_utf8_lossy(data);
+    match t {
+        b'h' | b'g' => text.to_string(),
+        b'x' => xdxf_to_html(&text),
+        b't' => format!(r#"<div class="sd-phonetic">[{}]</div>"#, escape_html(&text)),
+        b'r' => resources_html(&text),
+        t if t.is_ascii_lowercase() => format!(
+            r#"<div class="sd-text">{}</div>"#,
+            escape_html(&text).replace('\n', "<br/>")
+        ),
+        _ => String::new(),
+    }
+}
+
+/// 资源列表每行一个 "类型:文件名"，如 img:apple.png snd:apple.wav
+fn resources_html(text: &str) -> String {
+    text.lines()
+        .filter_map(|l| l.split_once(':'))
+        .map(|(kind, file)| {
+            let file = escape_html(file.trim());
+            match kind {
+                "img" => format!(r#"<img src="{file}"/>"#),
+                "snd" => format!(r#"<a href="sound://{file}">{file}</a>"#),
+                _ => format!(r#"<a href="{file}">{file}</a>"#),
+            }
+        })
+        .collect()
+}
+
+/// xdxf标记转换为html，k词头 ex例句 tr音标 abr缩写 gr语法 kref词条链接 c颜色
+fn xdxf_to_html(xdxf: &str) -> String {
+    static KREF_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?s)<kref[^>]*>(.*?)</kref>").unwrap());
+    static RREF_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?s)<rref[^>]*>(.*?)</rref>").unwrap());
+    static TAG_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"<(/?)(\w+)(?:\s+c="([^"]*)")?[^>]*>"#).unwrap());
+
+    let html = KREF_RE.replace_all(xdxf, r#"<a href="bword://$1">$1</a>"#);
+    let html = RREF_RE.replace_all(&html, r#"<a href="$1">$1</a>"#);
+    let html = TAG_RE.replace_all(&html, |caps: &Captures| {
+        let close = &caps[1] == "/";
+        let (tag, open) = match &caps[2] {
+            "k" => ("b", r#"<b class="k">"#.to_string()),
+            "ex" => ("span", r#"<span class="ex">"#.to_string()),
+            "tr" => ("span", r#"<span class="tr">["#.to_string()),
+            "abr" => ("i", r#"<i class="abr">"#.to_string()),
+            "gr" => ("i", r#"<i class="pos">"#.to_string()),
+            "dtrn" | "co" | "def" => ("span", format!(r#"<span class="{}">"#, &caps[2])),
+            "c" => (
+                "font",
+                format!(
+                    r#"<font color="{}">"#,
+                    escape_html(caps.get(3).map_or("green", |c| c.as_str()))
+                ),
+            ),
+            // a b i sup sub 等html中同名的标签保留
+            _ => return caps[0].to_string(),
+        };
+        match (close, &caps[2]) {
+            (true, "tr") => "]</span>".to_string(),
+            (true, _) => format!("</{tag}>"),
+            (false, _) => open,
+        }
+    });
+    html.trim().replace('\n', "<br/>")
+}
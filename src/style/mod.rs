@@ -0,0 +1,270 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::config::DICT_ASSETS;
+use crate::dictionary::Dictionary;
+use crate::resource::find_resource;
+use crate::rewrite::{local_path, resource_url};
+
+/// 词典的css和js资源路径: 优先使用配置，否则查找和词典同名的 {词典名}.css {词典名}.js
+pub fn dict_assets(dict: &dyn Dictionary) -> Vec<String> {
+    let name = &dict.info().name;
+    if let Some((_, assets)) = DICT_ASSETS.iter().find(|(n, _)| n == name) {
+        return assets.iter().map(|a| a.to_string()).collect();
+    }
+    [format!("{name}.css"), format!("{name}.js")]
+        .into_iter()
+        .filter(|asset| matches!(find_resource(dict, asset), Ok(Some(_))))
+        .collect()
+}
+
+/// 限定在词典结果容器内的选择器，查询结果用 <div class="mdict-dict" data-dict="..."> 包裹
+pub fn scope_selector(dict: &str) -> String {
+    format!(
+        r#".mdict-dict[data-dict="{}"]"#,
+        dict.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+/// 词典所有css合并，并给每条规则加上词典容器的选择器前缀，避免不同词典的样式互相影响
+pub fn scoped_css(dict: &dyn Dictionary) -> anyhow::Result<String> {
+    let name = &dict.info().name;
+    let scope = scope_selector(name);
+    let mut css = String::new();
+    for asset in dict_assets(dict).iter().filter(|a| a.ends_with(".css")) {
+        if let Some(data) = find_resource(dict, asset)? {
+            let base = asset.rsplit_once('/').map_or("", |(dir, _)| dir);
+            let source = String::from_utf8_lossy(&data);
+            let source = source.trim_start_matches('\u{feff}');
+            css.push_str(&format!("/* {asset} */\n"));
+            css.push_str(&prefix_rules(&rewrite_urls(source, name, base), &scope));
+            css.push('\n');
+        }
+    }
+    Ok(css)
+}
+
+/// css中 url(...) 的相对路径改为词典资源url
+fn rewrite_urls(css: &str, dict: &str, base: &str) -> String {
+    static URL_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"url\(\s*(['"]?)([^'")]*)(['"]?)\s*\)"#).unwrap());
+    URL_RE
+        .replace_all(css, |caps: &regex::Captures| match local_path(&caps[2]) {
+            Some(path) => {
+                let path = if base.is_empty() || path.starts_with('/') {
+                    path.to_string()
+                } else {
+                    format!("{base}/{path}")
+                };
+                format!(r#"url("{}")"#, resource_url(dict, &path))
+            }
+            None => caps[0].to_string(),
+        })
+        .to_string()
+}
+
+/// 给css规则的选择器加前缀，@media @supports 中的规则递归处理，@font-face @keyframes 等原样保留
+fn prefix_rules(css: &str, scope: &str) -> String {
+    let mut out = String::with_capacity(css.len() * 2);
+    let mut rest = css;
+    loop {
+        let trimmed = skip_space_and_comments(rest);
+        out.push_str(&rest[..rest.len() - trimmed.len()]);
+        rest = trimmed;
+        if rest.is_empty() {
+            break;
+        }
+
+        // 规则的前半部分，到 { 或 ; 为止
+        let Some(end) = find_top_level(rest, &['{', ';']) else {
+            out.push_str(rest);
+            break;
+        };
+        let prelude = &rest[..end];
+        if rest.as_bytes()[end] == b';' {
+            // @import @charset 等
+            out.push_str(&rest[..=end]);
+            rest = &rest[end + 1..];
+            continue;
+        }
+        // 没有闭合的 } 时剩下的内容都是block
+        let block_end = matching_brace(rest, end).unwrap_or(rest.len());
+        let block = &rest[end + 1..block_end];
+
+        let at_rule = prelude.trim_start().to_lowercase();
+        if at_rule.starts_with("@media")
+            || at_rule.starts_with("@supports")
+            || at_rule.starts_with("@document")
+            || at_rule.starts_with("@layer")
+        {
+            out.push_str(prelude);
+            out.push('{');
+            out.push_str(&prefix_rules(block, scope));
+            out.push('}');
+        } else if at_rule.starts_with('@') {
+            out.push_str(&rest[..(block_end + 1).min(rest.len())]);
+        } else {
+            out.push_str(&prefix_selectors(prelude, scope));
+            out.push('{');
+            out.push_str(block);
+            out.push('}');
+        }
+        rest = &rest[(block_end + 1).min(rest.len())..];
+    }
+    out
+}
+
+/// "a, body .b" -> "scope a, scope .b"，html body :root 替换为scope本身
+fn prefix_selectors(selectors: &str, scope: &str) -> String {
+    split_top_level(selectors, ',')
+        .into_iter()
+        .map(|selector| {
+            let selector = selector.trim();
+            for root in ["html", "body", ":root"] {
+                if let Some(rest) = selector.strip_prefix(root)
+                    && (rest.is_empty()
+                        || rest.starts_with([' ', '>', '.', '#', ':', '[', '+', '~']))
+                {
+                    let rest = rest.trim_start();
+                    let rest = rest.strip_prefix("body").map_or(rest, |r| r.trim_start());
+                    return format!("{scope} {rest}").trim_end().to_string();
+                }
+            }
+            format!("{scope} {selector}")
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn skip_space_and_comments(mut css: &str) -> &str {
+    loop {
+        css = css.trim_start();
+        match css.strip_prefix("/*") {
+            Some(comment) => css = comment.split_once("*/").map_or("", |(_, rest)| rest),
+            None => return css,
+        }
+    }
+}
+
+/// 第一个不在字符串、注释和括号中的目标字符的位置
+fn find_top_level(css: &str, targets: &[char]) -> Option<usize> {
+    let bytes = css.as_bytes();
+    let mut i = 0;
+    let mut depth = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' | b'\'' => i = skip_string(bytes, i),
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = css[i + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |p| i + 2 + p + 1);
+            }
+            b'(' | b'[' => depth += 1,
+            b')' | b']' => depth -= 1,
+            c if depth == 0 && targets.contains(&(c as char)) => return Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// open位置的 { 对应的 } 的位置
+fn matching_brace(css: &str, open: usize) -> Option<usize> {
+    let bytes = css.as_bytes();
+    let mut depth = 0;
+    let mut i = open;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' | b'\'' => i = skip_string(bytes, i),
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = css[i + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |p| i + 2 + p + 1);
+            }
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// 跳过以start位置的引号开始的字符串，返回结束引号的位置
+fn skip_string(bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() && bytes[i] != quote {
+        if bytes[i] == b'\\' {
+            i += 1;
+        }
+        i += 1;
+    }
+    i.min(bytes.len() - 1)
+}
+
+fn split_top_level(text: &str, sep: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut rest = text;
+    while let Some(i) = find_top_level(rest, &[sep]) {
+        parts.push(&rest[..i]);
+        rest = &rest[i + 1..];
+    }
+    parts.push(rest);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCOPE: &str = ".d";
+
+    #[test]
+    fn prefixes_selectors() {
+        assert_eq!(
+            prefix_rules("a, .b > c{color:red}", SCOPE),
+            ".d a, .d .b > c{color:red}"
+        );
+        assert_eq!(prefix_rules("body{margin:0}", SCOPE), ".d{margin:0}");
+        assert_eq!(prefix_rules("html body .x{}", SCOPE), ".d .x{}");
+        assert_eq!(prefix_rules(":root{--c:1}", SCOPE), ".d{--c:1}");
+        assert_eq!(prefix_rules("bodyx{}", SCOPE), ".d bodyx{}");
+    }
+
+    #[test]
+    fn nested_and_other_at_rules() {
+        assert_eq!(
+            prefix_rules("@media print{a{x:1} b{y:2}}", SCOPE),
+            "@media print{.d a{x:1} .d b{y:2}}"
+        );
+        assert_eq!(
+            prefix_rules("@font-face{font-family:f} @import url(a.css); a{}", SCOPE),
+            "@font-face{font-family:f} @import url(a.css); .d a{}"
+        );
+    }
+
+    #[test]
+    fn comments_and_strings() {
+        assert_eq!(
+            prefix_rules("/* a{} */ a[title=\"}\"]{content:\"{\"}", SCOPE),
+            "/* a{} */ .d a[title=\"}\"]{content:\"{\"}"
+        );
+    }
+
+    #[test]
+    fn unterminated_blocks() {
+        assert_eq!(prefix_rules("a {", SCOPE), ".d a{}");
+        assert_eq!(prefix_rules("a {color:red", SCOPE), ".d a{color:red}");
+        assert_eq!(prefix_rules("@font-face {", SCOPE), "@font-face {");
+        assert_eq!(prefix_rules("@media x{a{", SCOPE), "@media x{.d a{}}");
+        assert_eq!(prefix_rules("a", SCOPE), "a");
+    }
+}
@@ -1,25 +1,34 @@
-use nom::IResult;
-use nom::number::complete::{be_u8, be_u16};
+use std::path::{Component, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-// 解压缩这个地方优化一下
-pub fn fast_decrypt(encrypted: &[u8], key: &[u8]) -> Vec<u8> {
-    let mut buf = Vec::from(encrypted);
-    let mut prev = 0x36;
-    for i in 0..buf.len() {
-        let mut t = buf[i].rotate_left(4);
-        t = t ^ prev ^ (i as u8) ^ key[i % key.len()];
-        prev = buf[i];
-        buf[i] = t;
-    }
-    buf
+/// 转义html特殊字符，用于把文本放进html内容或属性值
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
 }
 
-/// nom parser
-pub fn text_len_parser_v2(input: &[u8]) -> IResult<&[u8], u16> {
-    let (input, len) = be_u16(input)?;
-    Ok((input, len + 1))
+/// 资源路径统一为小写、/分隔、不以/开头，mdd中的 "\Images\a.png" 和链接中的 "images/a.png" 一致
+pub fn normalize_resource_path(path: &str) -> String {
+    path.replace('\\', "/")
+        .trim_start_matches('/')
+        .to_lowercase()
 }
 
-pub fn text_len_parser_v1(input: &[u8]) -> IResult<&[u8], u8> {
-    be_u8(input)
+/// 只允许普通的相对路径，防止 ../ 访问目录之外的文件
+pub fn safe_relative_path(path: &str) -> Option<PathBuf> {
+    let relative = PathBuf::from(path);
+    relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+        .then_some(relative)
+}
+
+/// 当前的unix时间戳(秒)
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
 }
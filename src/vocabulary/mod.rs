@@ -0,0 +1,245 @@
+use std::sync::LazyLock;
+
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{OptionalExtension, Row, params};
+use serde_derive::Serialize;
+
+use crate::config::VOCABULARY_DB;
+use crate::util::unix_now;
+
+mod sm2;
+
+pub use sm2::{INITIAL_EASE, MAX_GRADE, Schedule};
+
+/// 一天的秒数，复习间隔以天为单位
+const DAY: i64 = 24 * 60 * 60;
+
+/// 生词本数据库的连接池，第一次使用时创建表
+static VOCABULARY_POOL: LazyLock<Pool<SqliteConnectionManager>> = LazyLock::new(|| {
+    let manager = SqliteConnectionManager::file(VOCABULARY_DB).with_init(|conn| {
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "busy_timeout", "5000")?;
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+        conn.execute_batch(&format!(
+            "create table if not exists WORD_LIST(
+                id integer primary key,
+                name text not null unique,
+                created integer not null
+            );
+            create table if not exists CARD(
+                id integer primary key,
+                list_id integer not null references WORD_LIST(id) on delete cascade,
+                word text not null collate nocase,
+                dictionary text,
+                added integer not null,
+                ease real not null default {INITIAL_EASE},
+                interval integer not null default 0,
+                repetitions integer not null default 0,
+                due integer not null,
+                last_review integer,
+                unique(list_id, word)
+            );
+            create index if not exists CARD_DUE on CARD(due);
+            create table if not exists REVIEW(
+                card_id integer not null references CARD(id) on delete cascade,
+                time integer not null,
+                grade integer not null
+            );"
+        ))
+    });
+    Pool::builder()
+        .max_size(4)
+        .build(manager)
+        .unwrap_or_else(|e| panic!("Failed to create connection pool for {VOCABULARY_DB}: {e}"))
+});
+
+/// 生词本，words为单词数，due为当前需要复习的单词数
+#[derive(Serialize, Debug)]
+pub struct WordList {
+    pub name: String,
+    pub created: i64,
+    pub words: usize,
+    pub due: usize,
+}
+
+/// 生词本中的一个单词，时间都是unix时间戳(秒)
+#[derive(Serialize, Debug)]
+pub struct Card {
+    pub id: i64,
+    pub list: String,
+    pub word: String,
+    // 收藏时所在的词典，复习时只显示该词典的释义，为None时显示所有词典的
+    pub dictionary: Option<String>,
+    pub added: i64,
+    #[serde(flatten)]
+    pub schedule: Schedule,
+    pub due: i64,
+    pub last_review: Option<i64>,
+}
+
+const CARD_COLUMNS: &str = "CARD.id, WORD_LIST.name, word, dictionary, added,
+    ease, interval, repetitions, due, last_review";
+
+fn connection() -> anyhow::Result<r2d2::PooledConnection<SqliteConnectionManager>> {
+    VOCABULARY_POOL
+        .get()
+        .map_err(|e| anyhow::anyhow!("Failed to get vocabulary connection: {}", e))
+}
+
+fn card(row: &Row) -> rusqlite::Result<Card> {
+    Ok(Card {
+        id: row.get(0)?,
+        list: row.get(1)?,
+        word: row.get(2)?,
+        dictionary: row.get(3)?,
+        added: row.get(4)?,
+        schedule: Schedule {
+            ease: row.get(5)?,
+            interval: row.get(6)?,
+            repetitions: row.get(7)?,
+        },
+        due: row.get(8)?,
+        last_review: row.get(9)?,
+    })
+}
+
+/// 所有生词本，按名称排序
+pub fn lists() -> anyhow::Result<Vec<WordList>> {
+    let conn = connection()?;
+    let mut stmt = conn.prepare(
+        "select name, created, count(CARD.id), coalesce(sum(CARD.due <= ?1), 0)
+        from WORD_LIST left join CARD on CARD.list_id = WORD_LIST.id
+        group by WORD_LIST.id order by name",
+    )?;
+    let lists = stmt
+        .query_map([unix_now()], |row| {
+            Ok(WordList {
+                name: row.get(0)?,
+                created: row.get(1)?,
+                words: row.get(2)?,
+                due: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(lists)
+}
+
+/// 把单词加入生词本，生词本不存在时创建，新单词马上可以复习
+/// 返回单词和是否新加入，已经在生词本中的单词保持原来的复习状态
+pub fn add_word(list: &str, word: &str, dictionary: Option<&str>) -> anyhow::Result<(Card, bool)> {
+    let mut conn = connection()?;
+    let tx = conn.transaction()?;
+    let now = unix_now();
+    tx.execute(
+        "insert or ignore into WORD_LIST(name, created) values (?1, ?2)",
+        params![list, now],
+    )?;
+    let added = tx.execute(
+        "insert or ignore into CARD(list_id, word, dictionary, added, due)
+        select id, ?2, ?3, ?4, ?4 from WORD_LIST where name = ?1",
+        params![list, word, dictionary, now],
+    )? > 0;
+    let card = tx.query_row(
+        &format!(
+            "select {CARD_COLUMNS} from CARD join WORD_LIST on CARD.list_id = WORD_LIST.id
+            where name = ?1 and word = ?2"
+        ),
+        params![list, word],
+        card,
+    )?;
+    tx.commit()?;
+    Ok((card, added))
+}
+
+/// 从生词本中删除单词，返回是否删除了
+pub fn remove_word(list: &str, word: &str) -> anyhow::Result<bool> {
+    let deleted = connection()?.execute(
+        "delete from CARD where word = ?2
+        and list_id = (select id from WORD_LIST where name = ?1)",
+        params![list, word],
+    )?;
+    Ok(deleted > 0)
+}
+
+/// 删除生词本和其中的单词，返回是否删除了
+pub fn delete_list(list: &str) -> anyhow::Result<bool> {
+    let deleted = connection()?.execute("delete from WORD_LIST where name = ?1", [list])?;
+    Ok(deleted > 0)
+}
+
+/// 生词本中的所有单词，按加入顺序，生词本不存在时返回None
+pub fn cards(list: &str) -> anyhow::Result<Option<Vec<Card>>> {
+    let conn = connection()?;
+    let exists = conn
+        .query_row(
+            "select 1 from WORD_LIST where name = ?1",
+            [list],
+            |_| Ok(()),
+        )
+        .optional()?;
+    if exists.is_none() {
+        return Ok(None);
+    }
+    let mut stmt = conn.prepare(&format!(
+        "select {CARD_COLUMNS} from CARD join WORD_LIST on CARD.list_id = WORD_LIST.id
+        where name = ?1 order by added, CARD.id"
+    ))?;
+    let cards = stmt
+        .query_map([list], card)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(Some(cards))
+}
+
+/// 已经到期需要复习的单词，最早到期的在前，list为None时包括所有生词本
+pub fn due_cards(list: Option<&str>, limit: usize) -> anyhow::Result<Vec<Card>> {
+    let conn = connection()?;
+    let mut stmt = conn.prepare(&format!(
+        "select {CARD_COLUMNS} from CARD join WORD_LIST on CARD.list_id = WORD_LIST.id
+        where due <= ?1 and (?2 is null or name = ?2)
+        order by due, CARD.id limit ?3"
+    ))?;
+    let cards = stmt
+        .query_map(params![unix_now(), list, limit], card)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(cards)
+}
+
+/// 记录一次复习的评分(0-5)，按SM-2计算下次复习时间，卡片不存在时返回None
+pub fn review(card_id: i64, grade: u8) -> anyhow::Result<Option<Card>> {
+    anyhow::ensure!(
+        grade <= MAX_GRADE,
+        "grade must be between 0 and {MAX_GRADE}"
+    );
+    let mut conn = connection()?;
+    let tx = conn.transaction()?;
+    let select = format!(
+        "select {CARD_COLUMNS} from CARD join WORD_LIST on CARD.list_id = WORD_LIST.id
+        where CARD.id = ?1"
+    );
+    let Some(current) = tx.query_row(&select, [card_id], card).optional()? else {
+        return Ok(None);
+    };
+
+    let now = unix_now();
+    let schedule = current.schedule.review(grade);
+    tx.execute(
+        "update CARD set ease = ?2, interval = ?3, repetitions = ?4, due = ?5, last_review = ?6
+        where id = ?1",
+        params![
+            card_id,
+            schedule.ease,
+            schedule.interval,
+            schedule.repetitions,
+            now + i64::from(schedule.interval) * DAY,
+            now
+        ],
+    )?;
+    tx.execute(
+        "insert into REVIEW(card_id, time, grade) values (?1, ?2, ?3)",
+        params![card_id, now, grade],
+    )?;
+    let updated = tx.query_row(&select, [card_id], card)?;
+    tx.commit()?;
+    Ok(Some(updated))
+}
@@ -0,0 +1,114 @@
+use serde_derive::Serialize;
+
+/// 新卡片的难度系数
+pub const INITIAL_EASE: f64 = 2.5;
+/// 难度系数的下限
+const MIN_EASE: f64 = 1.3;
+/// 评分的上限，0-5，3及以上算记住了
+pub const MAX_GRADE: u8 = 5;
+const PASS_GRADE: u8 = 3;
+
+/// 一张卡片的复习状态，interval单位为天
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Schedule {
+    pub ease: f64,
+    pub interval: u32,
+    pub repetitions: u32,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule {
+            ease: INITIAL_EASE,
+            interval: 0,
+            repetitions: 0,
+        }
+    }
+}
+
+impl Schedule {
+    /// SM-2算法: 按评分计算下一次的间隔和难度系数
+    /// 没记住(评分<3)时从头开始，间隔为1天；记住时间隔依次为1天、6天，之后乘以难度系数
+    pub fn review(self, grade: u8) -> Schedule {
+        let grade = grade.min(MAX_GRADE);
+        let (interval, repetitions) = if grade < PASS_GRADE {
+            (1, 0)
+        } else {
+            let interval = match self.repetitions {
+                0 => 1,
+                1 => 6,
+                _ => (self.interval as f64 * self.ease).round() as u32,
+            };
+            (interval, self.repetitions + 1)
+        };
+        let miss = f64::from(MAX_GRADE - grade);
+        let ease = (self.ease + 0.1 - miss * (0.08 + miss * 0.02)).max(MIN_EASE);
+        // 保留两位小数，避免浮点误差累积
+        let ease = (ease * 100.0).round() / 100.0;
+        Schedule {
+            ease,
+            interval,
+            repetitions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passing_reviews() {
+        let first = Schedule::default().review(4);
+        assert_eq!(
+            first,
+            Schedule {
+                ease: 2.5,
+                interval: 1,
+                repetitions: 1
+            }
+        );
+        let second = first.review(5);
+        assert_eq!(
+            second,
+            Schedule {
+                ease: 2.6,
+                interval: 6,
+                repetitions: 2
+            }
+        );
+        let third = second.review(3);
+        assert_eq!(third.interval, 16);
+        assert_eq!(third.repetitions, 3);
+        assert_eq!(third.ease, 2.46);
+    }
+
+    /// 没记住时重新开始，难度系数降低但不低于下限
+    #[test]
+    fn failed_review() {
+        let card = Schedule {
+            ease: 2.5,
+            interval: 15,
+            repetitions: 3,
+        };
+        let failed = card.review(1);
+        assert_eq!(
+            failed,
+            Schedule {
+                ease: 1.96,
+                interval: 1,
+                repetitions: 0
+            }
+        );
+        let mut card = Schedule::default();
+        for _ in 0..10 {
+            card = card.review(0);
+        }
+        assert_eq!(card.ease, MIN_EASE);
+    }
+
+    #[test]
+    fn grade_is_capped() {
+        assert_eq!(Schedule::default().review(9), Schedule::default().review(5));
+    }
+}